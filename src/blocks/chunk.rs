@@ -2,450 +2,550 @@
 
 use super::*;
 use crate::blocks::Block;
-use crate::textures::BlockTextureIndex;
-use crate::world::{Axis, Direction};
+use crate::maths::AABB;
+use crate::world::decoration::{self, PendingBlock};
+use crate::world::light::LightStorage;
+use crate::world::ores;
+use crate::world::terrain::TerrainGenerator;
+use crate::world::{ChunkCoords, Direction};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 pub const CHUNK_BLOCK_WIDTH: usize = 32;
 pub const CHUNK_SIZE: f32 = CHUNK_BLOCK_WIDTH as f32 * Block::WIDTH;
 
-/// Chunk contains a three-dimensional array of blocks
+/// Source of chunk versions. Versions come from one counter shared by every chunk so that a chunk
+/// that is unloaded and loaded again can never be mistaken for its older self.
+static NEXT_CHUNK_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_chunk_version() -> u64 {
+    NEXT_CHUNK_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Chunk contains a three-dimensional grid of blocks
 pub struct Chunk {
-    blocks: [[[Block; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH],
+    /// Shared with mesh jobs, which take snapshots of it. Changing a block while a job still has
+    /// the old blocks copies them first.
+    blocks: Arc<BlockStorage>,
+
+    /// Light levels of the chunk's blocks, shared with mesh jobs in the same way as the blocks.
+    light: Arc<LightStorage>,
+
+    /// Whether the chunk's light has been worked out yet. Until it has, there's no point in
+    /// keeping track of where light needs updating.
+    lit: bool,
+
+    /// Array positions of blocks that changed how light gets through them since the light was
+    /// last updated.
+    light_updates: Vec<(usize, usize, usize)>,
+
     // chunk_i: i64,
     // chunk_j: i64,
     // chunk_k: i64,
     /// The vertex buffer for the chunk mesh. Because it can't be initialized at first, we'll make
     /// it an Option so it can be set to Some when it's ready.
     block_mesh_buffer: Option<wgpu::Buffer>,
-    needs_mesh_update: bool,
     vertex_count: usize,
 
+    /// The vertex buffer for the faces of translucent blocks, which are drawn in a separate pass.
+    translucent_mesh_buffer: Option<wgpu::Buffer>,
+    translucent_vertex_count: usize,
+
+    /// Holds the chunk's position for the shaders, since mesh vertices are relative to the chunk.
+    /// Made along with the first mesh.
+    uniform_buffer: Option<wgpu::Buffer>,
+    uniform_bind_group: Option<wgpu::BindGroup>,
+
+    /// Changes every time the chunk's mesh goes out of date.
+    version: u64,
+
+    /// The version that the current mesh was made from, or None before the first mesh.
+    meshed_version: Option<u64>,
+
+    /// How coarsely the chunk is meshed, which depends on how far it is from the camera.
+    detail: LevelOfDetail,
+
     /// Constant chunk position based on chunk grid position and chunk size.
     chunk_position: [f32; 3],
 }
 
 impl Chunk {
-    pub fn generate(chunk_i: i64, chunk_j: i64, chunk_k: i64, _device: &wgpu::Device) -> Self {
-        let mut c = Self {
-            blocks: [[[Block::from(BlockType::Air); CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH];
-                CHUNK_BLOCK_WIDTH],
+    /// Makes a chunk full of air at the chunk grid position.
+    pub fn empty(chunk_i: i64, chunk_j: i64, chunk_k: i64) -> Self {
+        Self {
+            blocks: Arc::new(BlockStorage::new()),
+            light: Arc::new(LightStorage::new()),
+            lit: false,
+            light_updates: Vec::new(),
             // chunk_i,
             // chunk_j,
             // chunk_k,
             block_mesh_buffer: None,
             vertex_count: 0,
+            translucent_mesh_buffer: None,
+            translucent_vertex_count: 0,
+            uniform_buffer: None,
+            uniform_bind_group: None,
 
-            // set to true to make the initial mesh on first logic loop
-            needs_mesh_update: true,
+            // starts out without a mesh, so the first logic loop makes one
+            version: next_chunk_version(),
+            meshed_version: None,
+            detail: LevelOfDetail::Full,
 
             chunk_position: [
                 chunk_i as f32 * CHUNK_SIZE,
                 chunk_j as f32 * CHUNK_SIZE,
                 chunk_k as f32 * CHUNK_SIZE,
             ],
-        };
+        }
+    }
+
+    /// Generates the chunk at the chunk grid position using the terrain generator. Also returns
+    /// the blocks that decoration wanted to place outside of this chunk, like the parts of trees
+    /// that hang over its edges.
+    pub fn generate(
+        chunk_i: i64,
+        chunk_j: i64,
+        chunk_k: i64,
+        generator: &TerrainGenerator,
+    ) -> (Self, Vec<PendingBlock>) {
+        let mut c = Self::empty(chunk_i, chunk_j, chunk_k);
+
+        // world block position of this chunk's first block
+        let origin_y = chunk_j * CHUNK_BLOCK_WIDTH as i64;
+
+        let columns = generator.columns_for_chunk(chunk_i, chunk_k);
+        for (i, row) in columns.iter().enumerate() {
+            for (k, column) in row.iter().enumerate() {
+                for j in 0..CHUNK_BLOCK_WIDTH {
+                    let ty = generator.block_type_at(origin_y + j as i64, column);
+                    if ty != BlockType::Air {
+                        c.set(i, j, k, Block::from(ty));
+                    }
+                }
+            }
+        }
+
+        let coords = (chunk_i, chunk_j, chunk_k);
+        generator.caves().carve(&mut c, coords, &columns);
+        ores::place_ores(&mut c, generator, coords, &columns);
+        let overflow = decoration::decorate(&mut c, generator.seed(), coords, &columns);
 
+        (c, overflow)
+    }
+
+    /// Returns a hash of the block types in this chunk. Two chunks with the same blocks in the
+    /// same places have the same hash, which is handy for checking that generation is
+    /// deterministic.
+    pub fn content_hash(&self) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
         for i in 0..CHUNK_BLOCK_WIDTH {
-            for j in 0..CHUNK_BLOCK_WIDTH / 2 {
+            for j in 0..CHUNK_BLOCK_WIDTH {
                 for k in 0..CHUNK_BLOCK_WIDTH {
-                    c.set(i, j, k, Block::from(BlockType::Grass))
+                    self.blocks.block_type(i, j, k).hash(&mut hasher);
                 }
             }
         }
+        hasher.finish()
+    }
 
-        c
+    /// Returns the block at the array position.
+    pub fn at(&self, i: usize, j: usize, k: usize) -> Block {
+        self.blocks.get(i, j, k)
     }
 
-    // /// Returns the block at the array position.
-    // pub fn at(&self, i: usize, j: usize, k: usize) -> &Block {
-    //     &self.blocks[i][j][k]
-    // }
+    /// Returns the type of the block at the array position.
+    pub fn block_type_at(&self, i: usize, j: usize, k: usize) -> BlockType {
+        self.blocks.block_type(i, j, k)
+    }
 
-    /// Sets the block at the array index.
+    /// Sets the block at the array index. If the new block lets light through differently, or
+    /// gives off different light, than the old one, the light around it is updated the next time
+    /// the world updates light.
     pub fn set(&mut self, i: usize, j: usize, k: usize, b: Block) {
-        self.blocks[i][j][k] = b;
-        self.needs_mesh_update = true;
-    }
+        let old_type = self.blocks.block_type(i, j, k);
+        let new_type = b.block_type();
 
-    pub fn logic(&mut self, device: &wgpu::Device) {
-        // it'd be cool to put this in the `render` method, but `render` isn't provided a queue
-        if self.needs_mesh_update {
-            self.make_greedy_mesh(device);
-            self.needs_mesh_update = false;
-        }
-    }
+        Arc::make_mut(&mut self.blocks).set(i, j, k, b);
+        self.version = next_chunk_version();
 
-    /// Renders the Chunk. This method assumes that the block texture bind group has already been
-    /// bound to the render pass.
-    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
-        if let Some(vertex_buffer) = &self.block_mesh_buffer {
-            render_pass.set_vertex_buffer(
-                0,
-                vertex_buffer,
-                0,
-                self.vertex_count as u64 * ChunkMeshVertex::SIZE,
-            );
-            render_pass.draw(0..self.vertex_count as u32, 0..1);
+        let changes_light = old_type.lets_light_through() != new_type.lets_light_through()
+            || old_type.light_emission() != new_type.light_emission();
+        if self.lit && changes_light {
+            self.light_updates.push((i, j, k));
         }
     }
 
-    // TODO: create a queue (world-level, probably) that renders new blocks while a chunk adds them
-    // to its own greedy mesh, in case re-creating greedy meshes is too slow
-    fn make_greedy_mesh(&mut self, device: &wgpu::Device) {
-        let mut vertices = Vec::<ChunkMeshVertex>::new();
+    /// Returns the light levels of the chunk's blocks.
+    pub fn light(&self) -> &LightStorage {
+        &self.light
+    }
 
-        vertices.append(&mut self.get_greedy_mesh_along_axis(Axis::X));
-        vertices.append(&mut self.get_greedy_mesh_along_axis(Axis::Y));
-        vertices.append(&mut self.get_greedy_mesh_along_axis(Axis::Z));
+    /// Returns the light levels of the chunk's blocks for changing. Doesn't mark the mesh as out
+    /// of date on its own.
+    pub fn light_mut(&mut self) -> &mut LightStorage {
+        Arc::make_mut(&mut self.light)
+    }
 
-        // save the number of vertices
-        self.vertex_count = vertices.len();
+    /// Marks the chunk's light as worked out, so that blocks changing from now on update it.
+    pub fn mark_lit(&mut self) {
+        self.lit = true;
+    }
 
-        let vertex_slice = &vertices[..];
-        let casted_slice = bytemuck::cast_slice(vertex_slice);
+    /// Returns the array positions of the blocks whose light needs updating, and forgets them.
+    pub fn take_light_updates(&mut self) -> Vec<(usize, usize, usize)> {
+        std::mem::take(&mut self.light_updates)
+    }
 
-        // copy vertices to the vertex buffer
-        self.block_mesh_buffer =
-            Some(device.create_buffer_with_data(casted_slice, wgpu::BufferUsage::VERTEX));
+    /// Returns the storage the chunk's blocks are kept in.
+    pub fn storage(&self) -> &BlockStorage {
+        &self.blocks
     }
 
-    // TODO: potentially separate traversals by Direction instead of Axis to further cull adjacent
-    // or hidden faces
-    fn get_greedy_mesh_along_axis(&self, along_axis: Axis) -> Vec<ChunkMeshVertex> {
-        let mut vertices = Vec::<ChunkMeshVertex>::new();
+    /// Returns the chunk's version, which changes every time its mesh goes out of date.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
 
-        // `layer` is basically the layer along whichever axis we're traversing
-        for layer in 0..CHUNK_BLOCK_WIDTH {
-            let mut visited_mask = [[false; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH];
+    /// Returns true if the chunk's mesh is out of date.
+    pub fn needs_mesh_update(&self) -> bool {
+        self.meshed_version != Some(self.version)
+    }
 
-            for i in 0..CHUNK_BLOCK_WIDTH {
-                for j in 0..CHUNK_BLOCK_WIDTH {
-                    // the block at the starting point that we have to match
-                    let reference_block = self.get_block_along_layer(along_axis, layer, i, j);
+    /// Marks the chunk's mesh as out of date, such as when a block in a neighboring chunk changes
+    /// next to it.
+    pub fn request_mesh_update(&mut self) {
+        self.version = next_chunk_version();
+    }
 
-                    // decide if we should just skip over this block for meshing
-                    if reference_block.should_skip_mesh() || visited_mask[i][j] {
-                        continue;
-                    }
+    /// Returns how coarsely the chunk is meshed.
+    pub fn level_of_detail(&self) -> LevelOfDetail {
+        self.detail
+    }
 
-                    // just mark this spot as visited while we're on it
-                    visited_mask[i][j] = true;
-
-                    // starting_point should not be mut
-                    let starting_point = (i, j);
-
-                    // inclusive ending! ending_point is also included in the final mesh
-                    let mut ending_point = (i, j);
-
-                    // traverse along the "i" axis first, getting the ending_point's `i` (start
-                    // at/skip i + 1 because the block at i is the reference_block, which doesn't
-                    // need to be matched to itself, silly :))
-                    for (k, flag_row) in visited_mask.iter_mut().enumerate().skip(i + 1) {
-                        // if blocks are still the same type, move the ending_point
-                        if self
-                            .get_block_along_layer(along_axis, layer, k, j)
-                            .block_type
-                            == reference_block.block_type
-                        {
-                            // block types match, so we can move the ending_point!
-                            ending_point.0 = k;
-                            flag_row[j] = true;
-                        } else {
-                            // block types don't match, so don't advance ending_point, don't mark
-                            // this position as visited, do not pass go, do not collect $200, just
-                            // break
-                            break;
-                        }
-                    }
+    /// Sets how coarsely the chunk is meshed, marking its mesh as out of date if that changed.
+    /// Returns true if it did.
+    pub fn set_level_of_detail(&mut self, detail: LevelOfDetail) -> bool {
+        if self.detail == detail {
+            return false;
+        }
 
-                    // now, we have to traverse along both the "i" the "j" axis (up until
-                    // ending_point's "i" component), making sure all blocks still match
-                    // reference_block's type. if we can move along the "j" axis and all block
-                    // types are still the same, we can advance ending_point.
-                    //
-                    // why "j" axis first? for each iteration, we need to traverse along the i axis
-                    // to accurately decide if we can *extend* the mesh in the direction of j. we
-                    // start at j + 1 because it's already been decided that all blocks along j
-                    // match.
-                    for l in (j + 1)..CHUNK_BLOCK_WIDTH {
-                        // go as far as we can while block types match. if even one of the blocks
-                        // along the i axis here don't match, we have to stop. if all blocks in
-                        // this row match, we can advance ending_point's j component.
-                        //
-                        // remember, ending_point is inclusive, which is why we go up to and equal
-                        // to ending_point.0
-                        let mut should_advance = true;
-                        for (m, flag_row) in visited_mask
-                            .iter_mut()
-                            .enumerate()
-                            .take(ending_point.0 + 1)
-                            .skip(i)
-                        {
-                            if self
-                                .get_block_along_layer(along_axis, layer, m, l)
-                                .block_type
-                                != reference_block.block_type
-                            {
-                                // came across a mismatching block, so signal no advancing and
-                                // leave this loop
-                                should_advance = false;
-                                break;
-                            }
-                            flag_row[l] = true;
-                        }
-
-                        if should_advance {
-                            ending_point.1 = l;
-                        // then we'll move onto the next row
-                        } else {
-                            // if we came across a block that doesn't match in this row, we have to
-                            // reset all visited flags in this row
-                            for row_flags in
-                                visited_mask.iter_mut().take(ending_point.0 + 1).skip(i)
-                            {
-                                row_flags[l] = false;
-                            }
-
-                            // and leave this loop
-                            break;
-                        }
-                    }
+        self.detail = detail;
+        self.request_mesh_update();
+        true
+    }
 
-                    // now, at this point, we should have a starting_point and ending_point. we can
-                    // use this data to make our mesh:
-
-                    let width = ending_point.0 - starting_point.0 + 1;
-                    let height = ending_point.1 - starting_point.1 + 1;
-
-                    let (direction, other_direction) = match along_axis {
-                        Axis::X => (Direction::East, Direction::West),
-                        Axis::Y => (Direction::Up, Direction::Down),
-                        Axis::Z => (Direction::North, Direction::South),
-                    };
-                    let quad_start = match along_axis {
-                        Axis::X => (layer, starting_point.0, starting_point.1),
-                        Axis::Y => (starting_point.0, layer, starting_point.1),
-                        Axis::Z => (starting_point.0, starting_point.1, layer),
-                    };
-                    if let Ok(texture_layer) = BlockTextureIndex::from_type_and_direction(
-                        reference_block.block_type,
-                        direction,
-                    ) {
-                        vertices.append(&mut self.get_quad_face_vertices(
-                            quad_start,
-                            width,
-                            height,
-                            texture_layer,
-                            direction,
-                        ));
-                    }
-                    if let Ok(other_texture_layer) = BlockTextureIndex::from_type_and_direction(
-                        reference_block.block_type,
-                        other_direction,
-                    ) {
-                        vertices.append(&mut self.get_quad_face_vertices(
-                            quad_start,
-                            width,
-                            height,
-                            other_texture_layer,
-                            other_direction,
-                        ));
-                    }
-                }
+    /// Takes a snapshot of the chunk and its neighbors that can be meshed on another thread.
+    pub fn mesh_job(&self, coords: ChunkCoords, neighbors: &ChunkNeighbors) -> MeshJob {
+        let mut neighbor_blocks = [None, None, None, None, None, None];
+        let mut neighbor_light = [None, None, None, None, None, None];
+        let mut neighbor_detail = [self.detail; 6];
+        for &direction in Direction::ALL.iter() {
+            if let Some(c) = neighbors.get(direction) {
+                neighbor_blocks[direction as usize] = Some(Arc::clone(&c.blocks));
+                neighbor_light[direction as usize] = Some(Arc::clone(&c.light));
+                neighbor_detail[direction as usize] = c.detail;
             }
         }
 
-        vertices
+        MeshJob {
+            coords,
+            version: self.version,
+            blocks: Arc::clone(&self.blocks),
+            light: Arc::clone(&self.light),
+            detail: self.detail,
+            neighbors: neighbor_blocks,
+            neighbor_light,
+            neighbor_detail,
+        }
     }
 
-    fn get_block_along_layer(&self, along_axis: Axis, layer: usize, i: usize, j: usize) -> &Block {
-        match along_axis {
-            Axis::X => &self.blocks[layer][j][i], // becuase "i" is the z axis
-            Axis::Y => &self.blocks[i][layer][j],
-            Axis::Z => &self.blocks[i][j][layer],
+    /// Builds the chunk's mesh right away, on this thread.
+    pub fn make_mesh(&self, neighbors: &ChunkNeighbors) -> MeshResult {
+        self.mesh_job((0, 0, 0), neighbors).run()
+    }
+
+    /// Replaces the chunk's meshes with a finished mesh of one of the chunk's versions. The bind
+    /// group layout is the one made by `render::make_chunk_bind_group_layout`.
+    pub fn set_mesh(
+        &mut self,
+        device: &wgpu::Device,
+        chunk_bind_group_layout: &wgpu::BindGroupLayout,
+        mesh: &MeshResult,
+    ) {
+        if self.uniform_bind_group.is_none() {
+            self.make_uniform_bind_group(device, chunk_bind_group_layout);
         }
+
+        // save the number of vertices
+        self.vertex_count = mesh.vertices.len();
+        self.translucent_vertex_count = mesh.translucent_vertices.len();
+
+        // copy vertices to the vertex buffers, unless there's nothing to draw at all
+        self.block_mesh_buffer = Self::make_vertex_buffer(device, &mesh.vertices);
+        self.translucent_mesh_buffer = Self::make_vertex_buffer(device, &mesh.translucent_vertices);
+
+        self.meshed_version = Some(mesh.version);
     }
 
-    fn get_quad_face_vertices(
-        &self,
-        start_grid_pos: (usize, usize, usize),
-        width: usize,
-        height: usize,
-        texture_layer: BlockTextureIndex,
-        face_direction: Direction,
-    ) -> Vec<ChunkMeshVertex> {
-        // the starting block's exact position within the chunk (in other words, relative to the
-        // chunk, not the world)
-        let block_grid_pos = [
-            start_grid_pos.0 as f32 * Block::WIDTH,
-            start_grid_pos.1 as f32 * Block::WIDTH,
-            start_grid_pos.2 as f32 * Block::WIDTH,
-        ];
-
-        // the bottom, south-east vertex position of the cuboid
-        let base_vertex_pos = [
-            self.chunk_position[0] + block_grid_pos[0],
-            self.chunk_position[1] + block_grid_pos[1],
-            self.chunk_position[2] + block_grid_pos[2],
-        ];
-
-        let (quad_width, quad_height) = (width as f32 * Block::WIDTH, height as f32 * Block::WIDTH);
-
-        let (lower_left_pos, lower_right_pos, upper_right_pos, upper_left_pos) =
-            match face_direction {
-                Direction::North => {
-                    let z = base_vertex_pos[2] + Block::WIDTH;
-                    (
-                        [base_vertex_pos[0], base_vertex_pos[1], z],
-                        [base_vertex_pos[0] + quad_width, base_vertex_pos[1], z],
-                        [
-                            base_vertex_pos[0] + quad_width,
-                            base_vertex_pos[1] + quad_height,
-                            z,
-                        ],
-                        [base_vertex_pos[0], base_vertex_pos[1] + quad_height, z],
-                    )
-                }
-                Direction::South => {
-                    let z = base_vertex_pos[2];
-                    (
-                        [base_vertex_pos[0] + quad_width, base_vertex_pos[1], z],
-                        [base_vertex_pos[0], base_vertex_pos[1], z],
-                        [base_vertex_pos[0], base_vertex_pos[1] + quad_height, z],
-                        [
-                            base_vertex_pos[0] + quad_width,
-                            base_vertex_pos[1] + quad_height,
-                            z,
-                        ],
-                    )
-                }
-                Direction::East => {
-                    let x = base_vertex_pos[0];
-                    (
-                        [x, base_vertex_pos[1], base_vertex_pos[2]],
-                        [x, base_vertex_pos[1], base_vertex_pos[2] + quad_width],
-                        [
-                            x,
-                            base_vertex_pos[1] + quad_height,
-                            base_vertex_pos[2] + quad_width,
-                        ],
-                        [x, base_vertex_pos[1] + quad_height, base_vertex_pos[2]],
-                    )
-                }
-                Direction::West => {
-                    let x = base_vertex_pos[0] + Block::WIDTH;
-                    (
-                        [x, base_vertex_pos[1], base_vertex_pos[2] + quad_width],
-                        [x, base_vertex_pos[1], base_vertex_pos[2]],
-                        [x, base_vertex_pos[1] + quad_height, base_vertex_pos[2]],
-                        [
-                            x,
-                            base_vertex_pos[1] + quad_height,
-                            base_vertex_pos[2] + quad_width,
-                        ],
-                    )
-                }
-                Direction::Up => {
-                    let y = base_vertex_pos[1] + Block::WIDTH;
-                    (
-                        [base_vertex_pos[0] + quad_width, y, base_vertex_pos[2]],
-                        [base_vertex_pos[0], y, base_vertex_pos[2]],
-                        [base_vertex_pos[0], y, base_vertex_pos[2] + quad_height],
-                        [
-                            base_vertex_pos[0] + quad_width,
-                            y,
-                            base_vertex_pos[2] + quad_height,
-                        ],
-                    )
-                }
-                Direction::Down => {
-                    let y = base_vertex_pos[1];
-                    (
-                        [base_vertex_pos[0], y, base_vertex_pos[2]],
-                        [base_vertex_pos[0] + quad_width, y, base_vertex_pos[2]],
-                        [
-                            base_vertex_pos[0] + quad_width,
-                            y,
-                            base_vertex_pos[2] + quad_height,
-                        ],
-                        [base_vertex_pos[0], y, base_vertex_pos[2] + quad_height],
-                    )
-                }
-            };
-
-        let texture_layer_coord = texture_layer.to_tex_coord();
-
-        let (lower_left, lower_right, upper_right, upper_left) = (
-            ChunkMeshVertex {
-                position: lower_left_pos,
-                uv_coords: [0.0, height as f32],
-                texture_layer_coord,
-            },
-            ChunkMeshVertex {
-                position: lower_right_pos,
-                uv_coords: [width as f32, height as f32],
-                texture_layer_coord,
-            },
-            ChunkMeshVertex {
-                position: upper_right_pos,
-                uv_coords: [width as f32, 0.0],
-                texture_layer_coord,
-            },
-            ChunkMeshVertex {
-                position: upper_left_pos,
-                uv_coords: [0.0, 0.0],
-                texture_layer_coord,
-            },
+    fn make_uniform_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        chunk_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        let uniforms = ChunkUniforms {
+            position: self.chunk_position,
+            block_width: Block::WIDTH,
+        };
+        let buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[uniforms]),
+            wgpu::BufferUsage::UNIFORM,
         );
 
-        vec![
-            lower_left,
-            lower_right,
-            upper_left,
-            upper_left,
-            lower_right,
-            upper_right,
+        self.uniform_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: chunk_bind_group_layout,
+            bindings: &[wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &buffer,
+                    range: 0..std::mem::size_of::<ChunkUniforms>() as wgpu::BufferAddress,
+                },
+            }],
+            label: Some("chunk uniform bind group"),
+        }));
+        self.uniform_buffer = Some(buffer);
+    }
+
+    fn make_vertex_buffer(
+        device: &wgpu::Device,
+        vertices: &[ChunkMeshVertex],
+    ) -> Option<wgpu::Buffer> {
+        if vertices.is_empty() {
+            None
+        } else {
+            let casted_slice = bytemuck::cast_slice(vertices);
+            Some(device.create_buffer_with_data(casted_slice, wgpu::BufferUsage::VERTEX))
+        }
+    }
+
+    /// Returns the position of the middle of the chunk.
+    pub fn center(&self) -> [f32; 3] {
+        let half = CHUNK_SIZE / 2.0;
+        [
+            self.chunk_position[0] + half,
+            self.chunk_position[1] + half,
+            self.chunk_position[2] + half,
         ]
     }
+
+    /// Returns the box the chunk takes up in the world.
+    pub fn bounding_box(&self) -> AABB {
+        let half = CHUNK_SIZE / 2.0;
+        let center = self.center();
+        AABB {
+            center_pos: center.into(),
+            half_size: cgmath::Vector3::new(half, half, half),
+        }
+    }
+
+    /// Returns true if the chunk's mesh has any translucent faces.
+    pub fn has_translucent_mesh(&self) -> bool {
+        self.translucent_mesh_buffer.is_some()
+    }
+
+    /// Renders the Chunk's opaque and cutout blocks. This method assumes that the block texture
+    /// bind group and the quad index buffer have already been bound to the render pass.
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_mesh(render_pass, &self.block_mesh_buffer, self.vertex_count);
+    }
+
+    /// Renders the Chunk's translucent blocks. Should be called after every chunk's opaque blocks
+    /// have been drawn, with the translucent pipeline bound.
+    pub fn render_translucent<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_mesh(
+            render_pass,
+            &self.translucent_mesh_buffer,
+            self.translucent_vertex_count,
+        );
+    }
+
+    fn draw_mesh<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a Option<wgpu::Buffer>,
+        vertex_count: usize,
+    ) {
+        let (vertex_buffer, bind_group) = match (buffer, &self.uniform_bind_group) {
+            (Some(vertex_buffer), Some(bind_group)) => (vertex_buffer, bind_group),
+            _ => return,
+        };
+
+        render_pass.set_bind_group(2, bind_group, &[]);
+        render_pass.set_vertex_buffer(
+            0,
+            vertex_buffer,
+            0,
+            vertex_count as u64 * ChunkMeshVertex::SIZE,
+        );
+
+        // the shared index buffer only reaches so many quads, so big meshes are drawn a batch of
+        // quads at a time, starting each batch further along the vertex buffer
+        let quad_count = vertex_count / 4;
+        for first_quad in (0..quad_count).step_by(render::MAX_QUADS_PER_DRAW) {
+            let batch = (quad_count - first_quad).min(render::MAX_QUADS_PER_DRAW);
+            render_pass.draw_indexed(
+                0..(batch * render::QUAD_INDICES.len()) as u32,
+                (first_quad * 4) as i32,
+                0..1,
+            );
+        }
+    }
+}
+
+/// The chunks next to a chunk, which its mesher looks into to find out whether the faces on the
+/// chunk's borders are covered.
+#[derive(Default)]
+pub struct ChunkNeighbors<'a> {
+    chunks: [Option<&'a Chunk>; 6],
+}
+
+impl<'a> ChunkNeighbors<'a> {
+    /// Returns the neighboring chunk in the direction, if it's loaded.
+    pub fn get(&self, direction: Direction) -> Option<&'a Chunk> {
+        self.chunks[direction as usize]
+    }
+
+    /// Sets the neighboring chunk in the direction.
+    pub fn set(&mut self, direction: Direction, chunk: Option<&'a Chunk>) {
+        self.chunks[direction as usize] = chunk;
+    }
 }
 
+/// A corner of a quad in a chunk mesh, packed into two words to keep meshes small. The first holds
+/// the corner's position within the chunk, in blocks, along with the direction the quad faces and
+/// the corner's ambient occlusion. The second holds the texture layer and the light shining on the
+/// quad. Texture coordinates are worked out from the position and facing in the vertex shader.
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct ChunkMeshVertex {
-    pub position: [f32; 3],
-    pub uv_coords: [f32; 2],
-    pub texture_layer_coord: f32,
+    position_and_face: u32,
+    texture_and_light: u32,
 }
 
 impl ChunkMeshVertex {
     pub const SIZE: u64 = std::mem::size_of::<Self>() as wgpu::BufferAddress;
 
-    pub fn vertex_buffer_descriptors<'a>() -> &'a [wgpu::VertexBufferDescriptor<'a>] {
-        use std::mem::size_of;
+    /// Packs a vertex. Positions run from 0 to `CHUNK_BLOCK_WIDTH`, ambient occlusion from 0 to
+    /// 3, and the light is packed like `LightStorage::packed`.
+    pub fn new(
+        position: (usize, usize, usize),
+        face: Direction,
+        ambient_occlusion: u8,
+        texture_layer: u32,
+        light: u8,
+    ) -> Self {
+        Self {
+            position_and_face: position.0 as u32
+                | (position.1 as u32) << 6
+                | (position.2 as u32) << 12
+                | (face as u32) << 18
+                | (ambient_occlusion as u32 & 0b11) << 21,
+            texture_and_light: (texture_layer & 0xff) | (light as u32) << 8,
+        }
+    }
 
+    /// Returns the vertex's position within the chunk, in blocks.
+    pub fn position(self) -> (usize, usize, usize) {
+        let p = self.position_and_face;
+        (
+            (p & 0x3f) as usize,
+            (p >> 6 & 0x3f) as usize,
+            (p >> 12 & 0x3f) as usize,
+        )
+    }
+
+    /// Returns the direction that the vertex's quad faces.
+    pub fn face(self) -> Direction {
+        Direction::ALL[(self.position_and_face >> 18 & 0b111) as usize]
+    }
+
+    /// Returns the vertex's ambient occlusion, from 0 for boxed in to 3 for out in the open.
+    pub fn ambient_occlusion(self) -> u8 {
+        (self.position_and_face >> 21 & 0b11) as u8
+    }
+
+    pub fn texture_layer(self) -> u32 {
+        self.texture_and_light & 0xff
+    }
+
+    /// Returns the light shining on the vertex, packed like `LightStorage::packed`.
+    pub fn light(self) -> u8 {
+        (self.texture_and_light >> 8 & 0xff) as u8
+    }
+
+    pub fn vertex_buffer_descriptors<'a>() -> &'a [wgpu::VertexBufferDescriptor<'a>] {
         &[wgpu::VertexBufferDescriptor {
             stride: Self::SIZE,
             step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float3,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: size_of::<[f32; 5]>() as wgpu::BufferAddress,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float
-                },
-            ],
+            attributes: &[wgpu::VertexAttributeDescriptor {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Uint2,
+            }],
         }]
     }
 }
 
 unsafe impl bytemuck::Pod for ChunkMeshVertex {}
 unsafe impl bytemuck::Zeroable for ChunkMeshVertex {}
+
+/// Uniforms that are different for every chunk. Mesh vertices are relative to the chunk and
+/// measured in blocks, so these place them in the world.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ChunkUniforms {
+    position: [f32; 3],
+    block_width: f32,
+}
+
+unsafe impl bytemuck::Pod for ChunkUniforms {}
+unsafe impl bytemuck::Zeroable for ChunkUniforms {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chunk just below sea level, which has ground, water, caves and ores in it for most seeds.
+    const COORDS: ChunkCoords = (2, -1, -3);
+
+    fn generated_hash(generator: &TerrainGenerator, coords: ChunkCoords) -> u64 {
+        Chunk::generate(coords.0, coords.1, coords.2, generator)
+            .0
+            .content_hash()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_chunk() {
+        let first = TerrainGenerator::new(1234);
+        let second = TerrainGenerator::new(1234);
+
+        assert_eq!(
+            generated_hash(&first, COORDS),
+            generated_hash(&second, COORDS)
+        );
+    }
+
+    #[test]
+    fn different_seeds_generate_different_chunks() {
+        let first = TerrainGenerator::new(1234);
+        let second = TerrainGenerator::new(4321);
+
+        assert_ne!(
+            generated_hash(&first, COORDS),
+            generated_hash(&second, COORDS)
+        );
+    }
+}
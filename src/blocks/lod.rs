@@ -0,0 +1,322 @@
+use super::{BlockStorage, BlockType, CHUNK_BLOCK_WIDTH};
+
+/// How coarsely a chunk is meshed. Chunks far from the camera are meshed out of cells several
+/// blocks wide instead of single blocks, which takes far fewer vertices to draw.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LevelOfDetail {
+    /// Every block is meshed.
+    Full,
+
+    /// Cells of 2×2×2 blocks.
+    Half,
+
+    /// Cells of 4×4×4 blocks.
+    Quarter,
+
+    /// Cells of 8×8×8 blocks.
+    Eighth,
+}
+
+impl LevelOfDetail {
+    pub const ALL: [LevelOfDetail; 4] = [
+        LevelOfDetail::Full,
+        LevelOfDetail::Half,
+        LevelOfDetail::Quarter,
+        LevelOfDetail::Eighth,
+    ];
+
+    /// Returns the width, in blocks, of the cells chunks are meshed out of.
+    pub fn scale(self) -> usize {
+        match self {
+            LevelOfDetail::Full => 1,
+            LevelOfDetail::Half => 2,
+            LevelOfDetail::Quarter => 4,
+            LevelOfDetail::Eighth => 8,
+        }
+    }
+
+    /// Returns the number of cells along each side of a chunk.
+    pub fn cells_per_chunk(self) -> usize {
+        CHUNK_BLOCK_WIDTH / self.scale()
+    }
+
+    /// Returns the level of detail to mesh a chunk at, given its squared distance in chunks from
+    /// the chunk the camera is in.
+    pub fn for_distance_squared(distance_squared: i64) -> Self {
+        match distance_squared {
+            d if d <= 2 * 2 => LevelOfDetail::Full,
+            d if d <= 4 * 4 => LevelOfDetail::Half,
+            d if d <= 8 * 8 => LevelOfDetail::Quarter,
+            _ => LevelOfDetail::Eighth,
+        }
+    }
+}
+
+/// Picks the block type that stands in for the cell of `scale` blocks a side whose first block is
+/// at the array position. Cells that are more than half air are air. Otherwise the cell takes the
+/// type that's most often the top block of its columns, so grassy ground stays grassy from afar.
+pub fn downsample(
+    blocks: &BlockStorage,
+    scale: usize,
+    (i, j, k): (usize, usize, usize),
+) -> BlockType {
+    if scale == 1 {
+        return blocks.block_type(i, j, k);
+    }
+
+    let mut filled = 0;
+    let mut tops: Vec<(BlockType, usize)> = Vec::new();
+    for x in i..i + scale {
+        for z in k..k + scale {
+            let mut top = None;
+            for y in j..j + scale {
+                let ty = blocks.block_type(x, y, z);
+                if ty != BlockType::Air {
+                    filled += 1;
+                    top = Some(ty);
+                }
+            }
+
+            if let Some(top) = top {
+                match tops.iter_mut().find(|(ty, _)| *ty == top) {
+                    Some((_, count)) => *count += 1,
+                    None => tops.push((top, 1)),
+                }
+            }
+        }
+    }
+
+    if filled * 2 < scale * scale * scale {
+        return BlockType::Air;
+    }
+
+    // the first type to get the most columns wins, so ties always go the same way
+    let mut best = (BlockType::Air, 0);
+    for &(ty, count) in &tops {
+        if count > best.1 {
+            best = (ty, count);
+        }
+    }
+    best.0
+}
+
+/// Downsamples a whole chunk, returning the type of every cell in `cells[i][j][k]` order.
+pub fn downsample_chunk(blocks: &BlockStorage, detail: LevelOfDetail) -> Vec<BlockType> {
+    let scale = detail.scale();
+    let width = detail.cells_per_chunk();
+
+    let mut cells = Vec::with_capacity(width * width * width);
+    for i in 0..width {
+        for j in 0..width {
+            for k in 0..width {
+                cells.push(downsample(blocks, scale, (i * scale, j * scale, k * scale)));
+            }
+        }
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{Block, MeshJob, MeshResult};
+    use crate::world::light::LightStorage;
+    use crate::world::Direction;
+    use std::sync::Arc;
+
+    fn fill(
+        blocks: &mut BlockStorage,
+        from: (usize, usize, usize),
+        to: (usize, usize, usize),
+        ty: BlockType,
+    ) {
+        for i in from.0..to.0 {
+            for j in from.1..to.1 {
+                for k in from.2..to.2 {
+                    blocks.set(i, j, k, Block::from(ty));
+                }
+            }
+        }
+    }
+
+    fn mesh(
+        blocks: BlockStorage,
+        detail: LevelOfDetail,
+        neighbors: &[(Direction, BlockStorage, LevelOfDetail)],
+    ) -> MeshResult {
+        let mut job = MeshJob {
+            coords: (0, 0, 0),
+            version: 0,
+            blocks: Arc::new(blocks),
+            light: Arc::new(LightStorage::new()),
+            detail,
+            neighbors: Default::default(),
+            neighbor_light: Default::default(),
+            neighbor_detail: [detail; 6],
+        };
+        for (direction, neighbor_blocks, neighbor_detail) in neighbors {
+            let index = *direction as usize;
+            job.neighbors[index] = Some(Arc::new(neighbor_blocks.clone()));
+            job.neighbor_light[index] = Some(Arc::new(LightStorage::new()));
+            job.neighbor_detail[index] = *neighbor_detail;
+        }
+
+        job.run()
+    }
+
+    fn faces_pointing(result: &MeshResult, direction: Direction) -> usize {
+        result
+            .vertices
+            .iter()
+            .filter(|v| v.face() == direction)
+            .count()
+            / 4
+    }
+
+    #[test]
+    fn farther_chunks_get_less_detail() {
+        let details: Vec<_> = [0, 4, 5, 16, 17, 64, 65, 1000]
+            .iter()
+            .map(|&d| LevelOfDetail::for_distance_squared(d))
+            .collect();
+
+        assert_eq!(
+            details,
+            [
+                LevelOfDetail::Full,
+                LevelOfDetail::Full,
+                LevelOfDetail::Half,
+                LevelOfDetail::Half,
+                LevelOfDetail::Quarter,
+                LevelOfDetail::Quarter,
+                LevelOfDetail::Eighth,
+                LevelOfDetail::Eighth,
+            ]
+        );
+    }
+
+    #[test]
+    fn mostly_empty_cells_are_air() {
+        let mut blocks = BlockStorage::new();
+        fill(&mut blocks, (0, 0, 0), (2, 1, 2), BlockType::Stone);
+        fill(&mut blocks, (4, 0, 4), (6, 1, 6), BlockType::Stone);
+        blocks.set(4, 1, 4, Block::from(BlockType::Stone));
+
+        // exactly half full is kept, less than half isn't
+        assert_eq!(downsample(&blocks, 2, (0, 0, 0)), BlockType::Stone);
+        assert_eq!(downsample(&blocks, 4, (0, 0, 0)), BlockType::Air);
+        assert_eq!(downsample(&blocks, 2, (4, 0, 4)), BlockType::Stone);
+        assert_eq!(downsample(&blocks, 2, (8, 0, 8)), BlockType::Air);
+    }
+
+    #[test]
+    fn cells_take_the_type_on_top() {
+        let mut blocks = BlockStorage::new();
+        fill(&mut blocks, (0, 0, 0), (4, 3, 4), BlockType::Dirt);
+        fill(&mut blocks, (0, 3, 0), (4, 4, 4), BlockType::Grass);
+        // one column is topped with sand instead
+        blocks.set(0, 3, 0, Block::from(BlockType::Sand));
+
+        assert_eq!(downsample(&blocks, 4, (0, 0, 0)), BlockType::Grass);
+        assert_eq!(downsample(&blocks, 2, (0, 0, 0)), BlockType::Dirt);
+        assert_eq!(downsample(&blocks, 2, (0, 2, 0)), BlockType::Grass);
+        assert_eq!(downsample(&blocks, 1, (0, 3, 0)), BlockType::Sand);
+    }
+
+    #[test]
+    fn full_detail_downsamples_to_the_blocks() {
+        let mut blocks = BlockStorage::new();
+        blocks.set(3, 5, 7, Block::from(BlockType::Stone));
+        let cells = downsample_chunk(&blocks, LevelOfDetail::Full);
+
+        assert_eq!(cells.len(), CHUNK_BLOCK_WIDTH.pow(3));
+        let index = (3 * CHUNK_BLOCK_WIDTH + 5) * CHUNK_BLOCK_WIDTH + 7;
+        assert_eq!(cells[index], BlockType::Stone);
+        assert_eq!(cells.iter().filter(|&&ty| ty != BlockType::Air).count(), 1);
+    }
+
+    #[test]
+    fn coarse_meshes_are_made_of_whole_cells() {
+        for &detail in LevelOfDetail::ALL.iter() {
+            let scale = detail.scale();
+            let mut blocks = BlockStorage::new();
+            fill(&mut blocks, (8, 8, 8), (16, 16, 16), BlockType::Stone);
+
+            let result = mesh(blocks, detail, &[]);
+
+            // an 8 block cube is a single quad a side however it's meshed
+            assert_eq!(result.vertices.len(), 6 * 4, "{:?}", detail);
+            for vertex in &result.vertices {
+                let (x, y, z) = vertex.position();
+                assert!([8, 16].contains(&x) && [8, 16].contains(&y) && [8, 16].contains(&z));
+                assert_eq!((x % scale, y % scale, z % scale), (0, 0, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn small_details_disappear_from_far_away() {
+        let mut blocks = BlockStorage::new();
+        fill(&mut blocks, (0, 0, 0), (2, 2, 2), BlockType::Stone);
+
+        assert_eq!(
+            mesh(blocks.clone(), LevelOfDetail::Half, &[])
+                .vertices
+                .len(),
+            6 * 4
+        );
+        assert!(mesh(blocks, LevelOfDetail::Quarter, &[])
+            .vertices
+            .is_empty());
+    }
+
+    #[test]
+    fn borders_with_other_detail_levels_are_closed() {
+        let mut blocks = BlockStorage::new();
+        fill(&mut blocks, (0, 0, 0), (32, 32, 32), BlockType::Stone);
+
+        // a neighbor at the same level of detail covers the border...
+        let same = mesh(
+            blocks.clone(),
+            LevelOfDetail::Half,
+            &[(Direction::East, blocks.clone(), LevelOfDetail::Half)],
+        );
+        assert_eq!(faces_pointing(&same, Direction::East), 0);
+        assert_eq!(faces_pointing(&same, Direction::West), 1);
+
+        // ...but one meshed differently may not line up with it, so the border is drawn
+        for &neighbor_detail in &[LevelOfDetail::Full, LevelOfDetail::Eighth] {
+            let other = mesh(
+                blocks.clone(),
+                LevelOfDetail::Half,
+                &[(Direction::East, blocks.clone(), neighbor_detail)],
+            );
+            assert_eq!(faces_pointing(&other, Direction::East), 1);
+        }
+    }
+
+    #[test]
+    fn neighbors_are_downsampled_the_same_way() {
+        let mut blocks = BlockStorage::new();
+        fill(&mut blocks, (0, 0, 0), (32, 32, 32), BlockType::Stone);
+
+        // a single block on top of the chunk isn't enough to fill a cell
+        let mut above = BlockStorage::new();
+        above.set(0, 0, 0, Block::from(BlockType::Stone));
+
+        let full = mesh(
+            blocks.clone(),
+            LevelOfDetail::Full,
+            &[(Direction::Up, above.clone(), LevelOfDetail::Full)],
+        );
+        let half = mesh(
+            blocks,
+            LevelOfDetail::Half,
+            &[(Direction::Up, above, LevelOfDetail::Half)],
+        );
+
+        assert!(faces_pointing(&full, Direction::Up) > 1);
+        assert_eq!(faces_pointing(&half, Direction::Up), 1);
+    }
+}
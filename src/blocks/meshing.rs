@@ -0,0 +1,621 @@
+use super::lod::{self, LevelOfDetail};
+use super::{BlockStorage, BlockType, ChunkMeshVertex, RenderLayer, CHUNK_BLOCK_WIDTH};
+use crate::textures::BlockTextureIndex;
+use crate::world::light::{LightStorage, OPEN_SKY};
+use crate::world::{Axis, ChunkCoords, Direction};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Number of threads that mesh chunks in the background.
+const MESH_WORKER_COUNT: usize = 3;
+
+/// The corners of a face, as steps along `u` and `v` from its middle, in the order that ambient
+/// occlusion values are kept in.
+const FACE_CORNERS: [(i64, i64); 4] = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+
+/// A face the greedy mesher can see in a layer. Faces are only merged into a quad with faces that
+/// are exactly the same.
+#[derive(Copy, Clone, PartialEq)]
+struct Face {
+    block_type: BlockType,
+
+    /// How open each corner of the face is, from 0 for boxed in to 3 for out in the open, in the
+    /// order of `FACE_CORNERS`. Faces of coarse meshes are always out in the open.
+    ambient_occlusion: [u8; 4],
+
+    /// Light levels of the block in front of the face, packed like `LightStorage::packed`. Faces
+    /// of coarse meshes take the brightest of the blocks in front of them.
+    light: u8,
+}
+
+/// A snapshot of a chunk, and of the chunks around it, that can be meshed on any thread. Made by
+/// `Chunk::mesh_job`.
+pub struct MeshJob {
+    pub(super) coords: ChunkCoords,
+
+    /// The version of the chunk that the snapshot was taken from.
+    pub(super) version: u64,
+
+    pub(super) blocks: Arc<BlockStorage>,
+    pub(super) light: Arc<LightStorage>,
+
+    /// How coarsely to mesh the chunk.
+    pub(super) detail: LevelOfDetail,
+
+    /// Blocks of the neighboring chunks, indexed by `Direction`. None for chunks that aren't
+    /// loaded.
+    pub(super) neighbors: [Option<Arc<BlockStorage>>; 6],
+
+    /// Light of the neighboring chunks, indexed like `neighbors`.
+    pub(super) neighbor_light: [Option<Arc<LightStorage>>; 6],
+
+    /// How coarsely each neighboring chunk is meshed, indexed like `neighbors`. The two meshes on
+    /// either side of a border between different levels of detail don't line up, so faces on that
+    /// border are always drawn instead of leaving holes where one mesh sticks out past the other.
+    pub(super) neighbor_detail: [LevelOfDetail; 6],
+}
+
+/// A finished chunk mesh.
+pub struct MeshResult {
+    pub coords: ChunkCoords,
+
+    /// The version of the chunk that the mesh was made from. If the chunk has changed since, the
+    /// mesh is out of date and should be thrown away.
+    pub version: u64,
+
+    /// Faces of opaque and cutout blocks.
+    pub vertices: Vec<ChunkMeshVertex>,
+
+    /// Faces of translucent blocks, which are drawn in their own pass after everything else.
+    pub translucent_vertices: Vec<ChunkMeshVertex>,
+}
+
+/// A pool of threads that mesh chunks. Jobs are handed out to whichever worker is free, in the
+/// order they were submitted.
+pub struct MeshWorkers {
+    jobs: Option<Sender<MeshJob>>,
+    results: Receiver<MeshResult>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl MeshWorkers {
+    pub fn new() -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<MeshJob>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..MESH_WORKER_COUNT)
+            .map(|n| {
+                let jobs = Arc::clone(&job_receiver);
+                let results = result_sender.clone();
+
+                thread::Builder::new()
+                    .name(format!("mesh worker {}", n))
+                    .spawn(move || loop {
+                        // the lock is let go of before meshing so other workers can take jobs
+                        let job = match jobs.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => break, // the pool was dropped
+                        };
+
+                        if results.send(job.run()).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("couldn't start a mesh worker")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(job_sender),
+            results: result_receiver,
+            workers,
+        }
+    }
+
+    /// Queues the job to be meshed by the next free worker.
+    pub fn submit(&self, job: MeshJob) {
+        if let Some(jobs) = &self.jobs {
+            // only fails if every worker has stopped, in which case there's nothing to do anyway
+            let _ = jobs.send(job);
+        }
+    }
+
+    /// Returns every mesh that has been finished since the last call, without waiting for any.
+    pub fn finished(&self) -> impl Iterator<Item = MeshResult> + '_ {
+        self.results.try_iter()
+    }
+}
+
+impl Default for MeshWorkers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MeshWorkers {
+    fn drop(&mut self) {
+        // closing the job queue tells the workers to stop once they finish what they're doing
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl MeshJob {
+    /// Builds the chunk's mesh at its level of detail. Faces that are hidden by the blocks next to
+    /// them are left out, including faces on the chunk's borders that are covered by blocks in
+    /// neighboring chunks meshed at the same level of detail.
+    pub fn run(&self) -> MeshResult {
+        let mut result = MeshResult {
+            coords: self.coords,
+            version: self.version,
+            vertices: Vec::new(),
+            translucent_vertices: Vec::new(),
+        };
+
+        let cells = lod::downsample_chunk(&self.blocks, self.detail);
+        for &direction in Direction::ALL.iter() {
+            self.add_greedy_mesh_facing(direction, &cells, &mut result);
+        }
+
+        result
+    }
+
+    /// Makes a greedy mesh of every visible face that points in the direction and adds it to the
+    /// result. Faces of the same block type that sit next to each other in a layer are merged into
+    /// as few quads as possible. `cells` is the chunk downsampled to its level of detail; at full
+    /// detail, every cell is a block.
+    fn add_greedy_mesh_facing(
+        &self,
+        direction: Direction,
+        cells: &[BlockType],
+        result: &mut MeshResult,
+    ) {
+        let axis = direction.axis();
+        let scale = self.detail.scale();
+        let width = self.detail.cells_per_chunk();
+
+        // `layer` is basically the layer along whichever axis the faces point along
+        for layer in 0..width {
+            // every face in this layer that can be seen, or None where there isn't a face to draw.
+            // faces are cleared out as they're added to quads.
+            let mut mask = [[None; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH];
+            for (u, mask_row) in mask[..width].iter_mut().enumerate() {
+                for (v, face) in mask_row[..width].iter_mut().enumerate() {
+                    let (i, j, k) = Self::layer_to_block_position(axis, layer, u, v);
+                    let ty = cells[(i * width + j) * width + k];
+                    let (di, dj, dk) = direction.offset();
+                    let neighbor =
+                        self.cell_type_at(cells, i as i64 + di, j as i64 + dj, k as i64 + dk);
+
+                    if !ty.should_skip_mesh() && ty.shows_face_next_to(neighbor) {
+                        *face = Some(if scale == 1 {
+                            Face {
+                                block_type: ty,
+                                ambient_occlusion: self
+                                    .face_ambient_occlusion(layer, u, v, direction),
+                                light: self.light_at(i as i64 + di, j as i64 + dj, k as i64 + dk),
+                            }
+                        } else {
+                            Face {
+                                block_type: ty,
+                                ambient_occlusion: [3; 4],
+                                light: self.cell_face_light(layer, u, v, direction),
+                            }
+                        });
+                    }
+                }
+            }
+
+            for u in 0..width {
+                for v in 0..width {
+                    let face = match mask[u][v] {
+                        Some(face) => face,
+                        None => continue,
+                    };
+                    let ty = face.block_type;
+
+                    // stretch the quad along `u` for as long as the faces match...
+                    let mut quad_width = 1;
+                    while u + quad_width < width && mask[u + quad_width][v] == Some(face) {
+                        quad_width += 1;
+                    }
+
+                    // ...then along `v`, but only by whole rows that match all the way across
+                    let mut quad_height = 1;
+                    while v + quad_height < width
+                        && mask[u..u + quad_width]
+                            .iter()
+                            .all(|mask_row| mask_row[v + quad_height] == Some(face))
+                    {
+                        quad_height += 1;
+                    }
+
+                    for mask_row in mask[u..u + quad_width].iter_mut() {
+                        for merged in mask_row[v..v + quad_height].iter_mut() {
+                            *merged = None;
+                        }
+                    }
+
+                    if let Ok(texture_layer) =
+                        BlockTextureIndex::from_type_and_direction(ty, direction)
+                    {
+                        let vertices = match ty.render_layer() {
+                            RenderLayer::Opaque | RenderLayer::Cutout => &mut result.vertices,
+                            RenderLayer::Translucent => &mut result.translucent_vertices,
+                        };
+                        vertices.append(&mut self.get_quad_face_vertices(
+                            Self::layer_to_block_position(
+                                axis,
+                                layer * scale,
+                                u * scale,
+                                v * scale,
+                            ),
+                            (quad_width * scale, quad_height * scale, scale),
+                            texture_layer,
+                            &face,
+                            direction,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Converts a position in a layer of faces into a block's array position. `u` runs along the
+    /// width of the faces' quads and `v` along their height.
+    fn layer_to_block_position<T>(axis: Axis, layer: T, u: T, v: T) -> (T, T, T) {
+        match axis {
+            Axis::X => (layer, v, u),
+            Axis::Y => (u, layer, v),
+            Axis::Z => (u, v, layer),
+        }
+    }
+
+    /// Returns the type of the cell at a position relative to the chunk, in cells, which may be
+    /// just over one of its borders. Returns None if the cell is in a chunk that isn't loaded, or
+    /// in one that's meshed at a different level of detail.
+    fn cell_type_at(&self, cells: &[BlockType], i: i64, j: i64, k: i64) -> Option<BlockType> {
+        let scale = self.detail.scale();
+        let width = self.detail.cells_per_chunk();
+        let inside = |n: i64| n >= 0 && n < width as i64;
+        if inside(i) && inside(j) && inside(k) {
+            let (i, j, k) = (i as usize, j as usize, k as usize);
+            return Some(cells[(i * width + j) * width + k]);
+        }
+
+        let scale_i64 = scale as i64;
+        let (chunk, position) = Self::locate(i * scale_i64, j * scale_i64, k * scale_i64)?;
+        let direction = chunk?;
+        if self.neighbor_detail[direction as usize] != self.detail {
+            return None;
+        }
+
+        let blocks = self.neighbors[direction as usize].as_ref()?;
+        Some(lod::downsample(blocks, scale, position))
+    }
+
+    /// Returns the type of the block at a position relative to the chunk, which may be just over
+    /// one of its borders. Returns None for positions in chunks that aren't loaded, and for
+    /// positions past more than one border at once, like across an edge of the chunk, since only
+    /// the chunks sharing a face with this one are part of the snapshot.
+    fn block_type_at(&self, i: i64, j: i64, k: i64) -> Option<BlockType> {
+        let (chunk, (i, j, k)) = Self::locate(i, j, k)?;
+        let blocks = match chunk {
+            None => &self.blocks,
+            Some(direction) => self.neighbors[direction as usize].as_ref()?,
+        };
+
+        Some(blocks.block_type(i, j, k))
+    }
+
+    /// Returns the packed light levels at a position relative to the chunk, like
+    /// `block_type_at`. Blocks that aren't known are taken to be out in the open.
+    fn light_at(&self, i: i64, j: i64, k: i64) -> u8 {
+        let (chunk, (i, j, k)) = match Self::locate(i, j, k) {
+            Some(located) => located,
+            None => return OPEN_SKY,
+        };
+
+        let light = match chunk {
+            None => Some(&self.light),
+            Some(direction) => self.neighbor_light[direction as usize].as_ref(),
+        };
+        light.map_or(OPEN_SKY, |light| light.packed(i, j, k))
+    }
+
+    /// Finds a position relative to the chunk, which may be just over one of its borders. Returns
+    /// the direction of the neighboring chunk it's in, or None if it's in this chunk, along with
+    /// its array position in that chunk. Returns None for positions past more than one border.
+    #[allow(clippy::type_complexity)]
+    fn locate(i: i64, j: i64, k: i64) -> Option<(Option<Direction>, (usize, usize, usize))> {
+        let width = CHUNK_BLOCK_WIDTH as i64;
+        let outside = |n: i64, below: Direction, above: Direction| {
+            if n < 0 {
+                Some(below)
+            } else if n >= width {
+                Some(above)
+            } else {
+                None
+            }
+        };
+
+        let borders_crossed = [
+            outside(i, Direction::East, Direction::West),
+            outside(j, Direction::Down, Direction::Up),
+            outside(k, Direction::South, Direction::North),
+        ];
+
+        let chunk = match borders_crossed {
+            [None, None, None] => None,
+            [Some(direction), None, None]
+            | [None, Some(direction), None]
+            | [None, None, Some(direction)] => Some(direction),
+            _ => return None,
+        };
+
+        Some((
+            chunk,
+            (
+                i.rem_euclid(width) as usize,
+                j.rem_euclid(width) as usize,
+                k.rem_euclid(width) as usize,
+            ),
+        ))
+    }
+
+    /// Returns the light shining on the face of a cell in a layer, which is the brightest sky light
+    /// and block light of any of the blocks in front of the face.
+    fn cell_face_light(&self, layer: usize, u: usize, v: usize, direction: Direction) -> u8 {
+        let axis = direction.axis();
+        let scale = self.detail.scale() as i64;
+        let (di, dj, dk) = direction.offset();
+        let front = if di + dj + dk > 0 {
+            (layer as i64 + 1) * scale
+        } else {
+            layer as i64 * scale - 1
+        };
+
+        let (mut sky, mut block) = (0, 0);
+        for block_u in u as i64 * scale..(u as i64 + 1) * scale {
+            for block_v in v as i64 * scale..(v as i64 + 1) * scale {
+                let (i, j, k) = Self::layer_to_block_position(axis, front, block_u, block_v);
+                let light = self.light_at(i, j, k);
+                sky = sky.max(light & 0xf0);
+                block = block.max(light & 0x0f);
+            }
+        }
+
+        sky | block
+    }
+
+    /// Returns true if the block at the position, relative to the chunk, casts ambient occlusion.
+    /// Blocks that aren't known don't.
+    fn occludes(&self, (i, j, k): (i64, i64, i64)) -> bool {
+        self.block_type_at(i, j, k)
+            .is_some_and(|ty| !ty.is_see_through())
+    }
+
+    /// Works out the ambient occlusion of each corner of the face of the block at the position in
+    /// a layer. A corner gets darker for each of the three blocks touching it, in front of the
+    /// face, that are solid; a corner wedged between two solid blocks is as dark as it gets,
+    /// whatever is diagonal to it.
+    fn face_ambient_occlusion(
+        &self,
+        layer: usize,
+        u: usize,
+        v: usize,
+        direction: Direction,
+    ) -> [u8; 4] {
+        let axis = direction.axis();
+
+        // the layer of blocks in front of the face. only one of the offsets isn't zero.
+        let (di, dj, dk) = direction.offset();
+        let front = layer as i64 + di + dj + dk;
+        let (u, v) = (u as i64, v as i64);
+
+        let mut ambient_occlusion = [0; 4];
+        for (corner, &(du, dv)) in ambient_occlusion.iter_mut().zip(FACE_CORNERS.iter()) {
+            let side_u = self.occludes(Self::layer_to_block_position(axis, front, u + du, v));
+            let side_v = self.occludes(Self::layer_to_block_position(axis, front, u, v + dv));
+            let diagonal =
+                self.occludes(Self::layer_to_block_position(axis, front, u + du, v + dv));
+
+            *corner = if side_u && side_v {
+                0
+            } else {
+                3 - (side_u as u8 + side_v as u8 + diagonal as u8)
+            };
+        }
+
+        ambient_occlusion
+    }
+
+    /// Returns the four corners of a quad, in the order that `render::QUAD_INDICES` expects.
+    /// Positions are relative to the chunk and measured in blocks. The quad is `width` by `height`
+    /// blocks and sits on the side of the blocks it covers, which are `depth` blocks deep.
+    fn get_quad_face_vertices(
+        &self,
+        start_grid_pos: (usize, usize, usize),
+        (width, height, depth): (usize, usize, usize),
+        texture_layer: BlockTextureIndex,
+        face: &Face,
+        face_direction: Direction,
+    ) -> Vec<ChunkMeshVertex> {
+        // the bottom, south-east corner of the starting block
+        let (x, y, z) = start_grid_pos;
+
+        let (lower_left_pos, lower_right_pos, upper_right_pos, upper_left_pos) =
+            match face_direction {
+                Direction::North => {
+                    let z = z + depth;
+                    (
+                        (x, y, z),
+                        (x + width, y, z),
+                        (x + width, y + height, z),
+                        (x, y + height, z),
+                    )
+                }
+                Direction::South => (
+                    (x + width, y, z),
+                    (x, y, z),
+                    (x, y + height, z),
+                    (x + width, y + height, z),
+                ),
+                Direction::East => (
+                    (x, y, z),
+                    (x, y, z + width),
+                    (x, y + height, z + width),
+                    (x, y + height, z),
+                ),
+                Direction::West => {
+                    let x = x + depth;
+                    (
+                        (x, y, z + width),
+                        (x, y, z),
+                        (x, y + height, z),
+                        (x, y + height, z + width),
+                    )
+                }
+                Direction::Up => {
+                    let y = y + depth;
+                    (
+                        (x + width, y, z),
+                        (x, y, z),
+                        (x, y, z + height),
+                        (x + width, y, z + height),
+                    )
+                }
+                Direction::Down => (
+                    (x, y, z),
+                    (x + width, y, z),
+                    (x + width, y, z + height),
+                    (x, y, z + height),
+                ),
+            };
+
+        // which of the face's corners each vertex sits on, in the order of `FACE_CORNERS`. faces
+        // pointing the other way along an axis are mirrored along `u`.
+        let corners = match face_direction {
+            Direction::North | Direction::East | Direction::Down => [0, 1, 2, 3],
+            Direction::South | Direction::West | Direction::Up => [1, 0, 3, 2],
+        };
+        let vertex = |position, corner: usize| {
+            ChunkMeshVertex::new(
+                position,
+                face_direction,
+                face.ambient_occlusion[corners[corner]],
+                texture_layer as u32,
+                face.light,
+            )
+        };
+
+        let (lower_left, lower_right, upper_right, upper_left) = (
+            vertex(lower_left_pos, 0),
+            vertex(lower_right_pos, 1),
+            vertex(upper_right_pos, 2),
+            vertex(upper_left_pos, 3),
+        );
+
+        // split the quad along whichever diagonal joins the lighter corners, so a dark corner
+        // fades out evenly instead of being smeared along the diagonal. the indices always split
+        // the quad between its second and third vertices.
+        if lower_left.ambient_occlusion() + upper_right.ambient_occlusion()
+            <= lower_right.ambient_occlusion() + upper_left.ambient_occlusion()
+        {
+            vec![lower_left, lower_right, upper_left, upper_right]
+        } else {
+            vec![lower_right, upper_right, lower_left, upper_left]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::Block;
+
+    const LAST: usize = CHUNK_BLOCK_WIDTH - 1;
+
+    fn blocks_of(placed: &[((usize, usize, usize), BlockType)]) -> BlockStorage {
+        let mut blocks = BlockStorage::new();
+        for &((i, j, k), ty) in placed {
+            blocks.set(i, j, k, Block::from(ty));
+        }
+        blocks
+    }
+
+    /// Meshes the blocks at full detail and returns how many quads the mesh has.
+    fn quad_count(blocks: BlockStorage, neighbors: &[(Direction, BlockStorage)]) -> usize {
+        let mut job = MeshJob {
+            coords: (0, 0, 0),
+            version: 0,
+            blocks: Arc::new(blocks),
+            light: Arc::new(LightStorage::new()),
+            detail: LevelOfDetail::Full,
+            neighbors: Default::default(),
+            neighbor_light: Default::default(),
+            neighbor_detail: [LevelOfDetail::Full; 6],
+        };
+        for (direction, neighbor_blocks) in neighbors {
+            job.neighbors[*direction as usize] = Some(Arc::new(neighbor_blocks.clone()));
+            job.neighbor_light[*direction as usize] = Some(Arc::new(LightStorage::new()));
+        }
+
+        let result = job.run();
+        assert_eq!(result.vertices.len() % 4, 0);
+        (result.vertices.len() + result.translucent_vertices.len()) / 4
+    }
+
+    #[test]
+    fn a_single_block_has_six_quads() {
+        let blocks = blocks_of(&[((5, 5, 5), BlockType::Stone)]);
+        assert_eq!(quad_count(blocks, &[]), 6);
+    }
+
+    #[test]
+    fn faces_of_a_bar_merge() {
+        let blocks = blocks_of(&[((5, 5, 5), BlockType::Stone), ((6, 5, 5), BlockType::Stone)]);
+        assert_eq!(quad_count(blocks, &[]), 6);
+    }
+
+    #[test]
+    fn buried_blocks_have_no_quads() {
+        let mut placed = Vec::new();
+        for i in 4..7 {
+            for j in 4..7 {
+                for k in 4..7 {
+                    placed.push(((i, j, k), BlockType::Stone));
+                }
+            }
+        }
+
+        // the block in the middle adds nothing to the cube's six sides
+        assert_eq!(quad_count(blocks_of(&placed), &[]), 6);
+    }
+
+    #[test]
+    fn faces_covered_by_a_loaded_neighbor_are_culled() {
+        let blocks = blocks_of(&[((0, 5, 5), BlockType::Stone)]);
+        let neighbor = blocks_of(&[((LAST, 5, 5), BlockType::Stone)]);
+
+        assert_eq!(quad_count(blocks, &[(Direction::East, neighbor)]), 5);
+    }
+
+    #[test]
+    fn faces_next_to_unloaded_chunks_are_drawn() {
+        let blocks = blocks_of(&[((0, 5, 5), BlockType::Stone)]);
+        assert_eq!(quad_count(blocks, &[]), 6);
+    }
+
+    #[test]
+    fn different_block_types_dont_merge() {
+        let blocks = blocks_of(&[((5, 5, 5), BlockType::Stone), ((6, 5, 5), BlockType::Dirt)]);
+
+        // the faces between them are hidden, and the rest stay a quad per block
+        assert_eq!(quad_count(blocks, &[]), 10);
+    }
+}
@@ -1,10 +1,21 @@
 pub mod chunk;
+pub mod lod;
+pub mod meshing;
+pub mod registry;
 pub mod render;
+pub mod storage;
 pub mod textures;
 
 pub use self::chunk::*;
+pub use self::lod::LevelOfDetail;
+pub use self::meshing::*;
+pub use self::storage::*;
 pub use self::textures::*;
 
+use crate::traits::Persist;
+use crate::world::save::{Reader, SaveError};
+use registry::{BlockDefinition, BlockDrop, Transparency};
+
 #[derive(Clone, Copy, Debug)]
 pub struct Block {
     block_type: BlockType,
@@ -29,33 +40,208 @@ impl From<BlockType> for Block {
 impl Block {
     pub const WIDTH: f32 = 0.5;
 
-    pub fn lifespan_of(ty: BlockType) -> f32 {
-        match ty {
-            BlockType::Dirt => 10.0,
-            BlockType::Grass => 15.0,
-            BlockType::Stone => 50.0,
-            BlockType::Sand => 5.0,
-            BlockType::Air => 0.0,
-            _ => {
-                eprintln!("BlockType `{:?}` not implemented. Lifespan unknown!", ty);
-                0.0
-            }
+    /// Makes a block with the given state, such as one that was loaded from a save.
+    pub fn new(
+        block_type: BlockType,
+        health: f32,
+        position_offset: Option<cgmath::Vector3<f32>>,
+    ) -> Self {
+        Self {
+            block_type,
+            health,
+            position_offset,
         }
     }
 
+    /// Returns how much health a new block of the type starts with, which is its hardness.
+    pub fn lifespan_of(ty: BlockType) -> f32 {
+        ty.definition().hardness
+    }
+
+    pub fn block_type(&self) -> BlockType {
+        self.block_type
+    }
+
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    pub fn position_offset(&self) -> Option<cgmath::Vector3<f32>> {
+        self.position_offset
+    }
+
     pub fn should_skip_mesh(&self) -> bool {
-        self.block_type == BlockType::Air
+        self.block_type.should_skip_mesh()
     }
 
     pub fn is_see_through(&self) -> bool {
-        match self.block_type {
-            BlockType::Air | BlockType::Glass => true,
-            _ => false,
+        self.block_type.is_see_through()
+    }
+}
+
+impl Persist for Block {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.block_type.id());
+        bytes.extend_from_slice(&self.health.to_le_bytes());
+        match self.position_offset {
+            Some(offset) => {
+                bytes.push(1);
+                offset.write_to(bytes);
+            }
+            None => bytes.push(0),
         }
     }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        let block_type = reader.read_block_type()?;
+        let health = reader.read_f32()?;
+        let position_offset = if reader.read_bool()? {
+            Some(cgmath::Vector3::read_from(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Self::new(block_type, health, position_offset))
+    }
+}
+
+impl BlockType {
+    /// Every block type, in the order of their ids.
+    pub const ALL: [BlockType; 21] = [
+        BlockType::Air,
+        BlockType::Sand,
+        BlockType::Dirt,
+        BlockType::Grass,
+        BlockType::Stone,
+        BlockType::Cobblestone,
+        BlockType::CoalOre,
+        BlockType::IronOre,
+        BlockType::CopperOre,
+        BlockType::GoldOre,
+        BlockType::DiamondOre,
+        BlockType::Wood,
+        BlockType::WoodPlanks,
+        BlockType::Furnace,
+        BlockType::Leaves,
+        BlockType::Glass,
+        BlockType::Water,
+        BlockType::Snow,
+        BlockType::Ice,
+        BlockType::Crystal,
+        BlockType::LitFurnace,
+    ];
+
+    /// Returns the id of the block type that is written to saves. Ids must never change once
+    /// they're in use; new block types get new ids at the end.
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the block type with the save id, if there is one.
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    /// Returns the name that the block type goes by in block definitions.
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockType::Air => "air",
+            BlockType::Sand => "sand",
+            BlockType::Dirt => "dirt",
+            BlockType::Grass => "grass",
+            BlockType::Stone => "stone",
+            BlockType::Cobblestone => "cobblestone",
+            BlockType::CoalOre => "coal_ore",
+            BlockType::IronOre => "iron_ore",
+            BlockType::CopperOre => "copper_ore",
+            BlockType::GoldOre => "gold_ore",
+            BlockType::DiamondOre => "diamond_ore",
+            BlockType::Wood => "wood",
+            BlockType::WoodPlanks => "wood_planks",
+            BlockType::Furnace => "furnace",
+            BlockType::Leaves => "leaves",
+            BlockType::Glass => "glass",
+            BlockType::Water => "water",
+            BlockType::Snow => "snow",
+            BlockType::Ice => "ice",
+            BlockType::Crystal => "crystal",
+            BlockType::LitFurnace => "lit_furnace",
+        }
+    }
+
+    /// Returns the block type with the name, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|ty| ty.name() == name)
+    }
+
+    /// Returns the block type's definition from the block registry.
+    pub fn definition(self) -> &'static BlockDefinition {
+        registry::registry().get(self)
+    }
+
+    pub fn should_skip_mesh(self) -> bool {
+        self.definition().transparency == Transparency::Invisible
+    }
+
+    pub fn is_see_through(self) -> bool {
+        self.definition().transparency != Transparency::Opaque
+    }
+
+    /// Returns true if light can shine through blocks of this type.
+    pub fn lets_light_through(self) -> bool {
+        self.is_see_through()
+    }
+
+    /// Returns the level of block light that blocks of this type give off, or 0 for blocks that
+    /// don't glow.
+    pub fn light_emission(self) -> u8 {
+        self.definition().light_emission
+    }
+
+    /// Returns what breaking a block of this type gives.
+    pub fn drops(self) -> &'static [BlockDrop] {
+        &self.definition().drops
+    }
+
+    /// Returns the render pass that faces of this type are drawn in. Invisible blocks aren't drawn
+    /// at all, but count as translucent.
+    pub fn render_layer(self) -> RenderLayer {
+        match self.definition().transparency {
+            Transparency::Opaque => RenderLayer::Opaque,
+            Transparency::Cutout => RenderLayer::Cutout,
+            Transparency::Translucent | Transparency::Invisible => RenderLayer::Translucent,
+        }
+    }
+
+    /// Returns true if a face of a block of this type should be drawn when it's next to a block
+    /// of the neighbor's type. Faces are hidden behind opaque blocks, and between two blocks of
+    /// the same translucent type, like the inside of a body of water. Cutout blocks like leaves
+    /// keep the faces between them, since they can be seen through the holes. A neighbor of None
+    /// isn't known yet, so faces next to it are drawn.
+    pub fn shows_face_next_to(self, neighbor: Option<BlockType>) -> bool {
+        match neighbor {
+            Some(neighbor) if neighbor == self => self.render_layer() == RenderLayer::Cutout,
+            Some(neighbor) => neighbor.is_see_through(),
+            None => true,
+        }
+    }
+}
+
+/// How the faces of a block type are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderLayer {
+    /// Drawn first, hiding everything behind it.
+    Opaque,
+
+    /// Drawn along with opaque blocks, except that pixels of the texture with low alpha are left
+    /// out entirely, like the gaps between leaves.
+    Cutout,
+
+    /// Blended over everything else after opaque blocks are drawn, like glass and water.
+    Translucent,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[allow(dead_code)]
 pub enum BlockType {
     Air,
@@ -74,4 +260,10 @@ pub enum BlockType {
     Furnace,
     Leaves,
     Glass,
+    Water,
+    Snow,
+    Ice,
+    Crystal,
+    /// A furnace with a fire going in it. Unlike a plain furnace, it gives off light.
+    LitFurnace,
 }
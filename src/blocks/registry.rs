@@ -0,0 +1,438 @@
+use super::BlockType;
+use crate::defs::{parse_sections, text, Section, Value};
+use crate::items::{Item, Resource};
+use crate::textures::BlockTextureIndex;
+use crate::world::light::MAX_LIGHT;
+use once_cell::sync::OnceCell;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+/// Where the block definitions are read from, relative to the working directory.
+const DEFINITIONS_PATH: &str = "assets/blocks.defs";
+
+/// A copy of the block definitions built into the game, for when there's no definitions file to
+/// read.
+const BUILT_IN_DEFINITIONS: &str = include_str!("../../assets/blocks.defs");
+const BUILT_IN_DEFINITIONS_NAME: &str = "built-in blocks.defs";
+
+/// The registry every block type looks itself up in.
+static REGISTRY: OnceCell<BlockRegistry> = OnceCell::new();
+
+/// Finds the texture a definition names, or returns None if there's no texture by that name to
+/// load.
+pub type TextureResolver<'a> = dyn Fn(&str) -> Option<BlockTextureIndex> + 'a;
+
+/// Reads and checks the block definitions in `assets/blocks.defs`, then installs them so that
+/// block types can look themselves up. If the file isn't there, the copy built into the game is
+/// used instead. Every texture the definitions use has to be one that `textures` can find. Does
+/// nothing if the definitions are already installed. Call this when the game starts so that
+/// mistakes in the definitions are reported before anything needs them.
+pub fn init(textures: &TextureResolver) -> Result<(), DefinitionError> {
+    if REGISTRY.get().is_some() {
+        return Ok(());
+    }
+
+    let registry = match std::fs::read_to_string(DEFINITIONS_PATH) {
+        Ok(source) => BlockRegistry::parse(DEFINITIONS_PATH, &source, textures)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!(
+                "`{}` isn't there, so the built-in block definitions are used",
+                DEFINITIONS_PATH
+            );
+            BlockRegistry::parse(BUILT_IN_DEFINITIONS_NAME, BUILT_IN_DEFINITIONS, textures)?
+        }
+        Err(e) => {
+            return Err(DefinitionError {
+                path: DEFINITIONS_PATH.to_string(),
+                line: None,
+                message: e.to_string(),
+            })
+        }
+    };
+
+    // if another thread got there first, its registry is kept
+    let _ = REGISTRY.set(registry);
+    Ok(())
+}
+
+/// Returns the installed block registry. `init` has to have been called first, so that mistakes
+/// in the definitions are reported when the game starts rather than whenever a block type is first
+/// looked up.
+#[cfg(not(test))]
+pub fn registry() -> &'static BlockRegistry {
+    REGISTRY
+        .get()
+        .expect("block types were looked up before `registry::init` was called")
+}
+
+/// Tests don't call `init`, so the built-in definitions are installed the first time one looks a
+/// block type up.
+#[cfg(test)]
+pub fn registry() -> &'static BlockRegistry {
+    REGISTRY.get_or_init(|| {
+        let textures = |name: &str| BlockTextureIndex::from_name(name);
+        BlockRegistry::parse(BUILT_IN_DEFINITIONS_NAME, BUILT_IN_DEFINITIONS, &textures)
+            .unwrap_or_else(|e| panic!("couldn't load block definitions: {}", e))
+    })
+}
+
+/// How much of what's behind a block can be seen through it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    /// Nothing behind it can be seen.
+    Opaque,
+
+    /// Parts of its texture are completely see-through and the rest is opaque, like leaves.
+    Cutout,
+
+    /// Its texture is blended over what's behind it, like glass and water.
+    Translucent,
+
+    /// It's never drawn at all, like air.
+    Invisible,
+}
+
+/// Something a block gives when it's broken.
+#[derive(Debug, Copy, Clone)]
+pub enum BlockDrop {
+    Block(BlockType),
+    Resource(Resource),
+}
+
+impl BlockDrop {
+    /// Makes the item that's dropped.
+    pub fn to_item(self) -> Item {
+        match self {
+            BlockDrop::Block(ty) => Item::Block(super::Block::from(ty)),
+            BlockDrop::Resource(resource) => Item::Resource(resource),
+        }
+    }
+}
+
+/// The textures on the faces of a block type.
+#[derive(Debug, Copy, Clone)]
+pub struct FaceTextures {
+    pub top: BlockTextureIndex,
+    pub side: BlockTextureIndex,
+    pub bottom: BlockTextureIndex,
+}
+
+/// Everything there is to know about a block type.
+#[derive(Debug)]
+pub struct BlockDefinition {
+    /// How much damage a block of this type takes to break.
+    pub hardness: f32,
+
+    pub transparency: Transparency,
+
+    /// None for block types that are invisible.
+    pub textures: Option<FaceTextures>,
+
+    /// What a block of this type gives when it's broken.
+    pub drops: Vec<BlockDrop>,
+
+    /// The level of block light that blocks of this type give off.
+    pub light_emission: u8,
+}
+
+/// The definitions of every block type.
+#[derive(Debug)]
+pub struct BlockRegistry {
+    /// Indexed by block type id.
+    definitions: Vec<BlockDefinition>,
+}
+
+impl BlockRegistry {
+    /// Parses block definitions and checks that they're complete: every block type has to be
+    /// defined, and every texture they use has to be found by `textures`. `path` is only used in
+    /// error messages.
+    pub fn parse(
+        path: &str,
+        source: &str,
+        textures: &TextureResolver,
+    ) -> Result<Self, DefinitionError> {
+        let error = |line: Option<usize>, message: String| DefinitionError {
+            path: path.to_string(),
+            line,
+            message,
+        };
+
+        let mut definitions: Vec<Option<BlockDefinition>> =
+            BlockType::ALL.iter().map(|_| None).collect();
+
+        for section in parse_sections(source).map_err(|(line, e)| error(Some(line), e))? {
+            let ty = BlockType::from_name(&section.name).ok_or_else(|| {
+                error(
+                    Some(section.line),
+                    format!("unknown block type `{}`", section.name),
+                )
+            })?;
+            if definitions[ty.id() as usize].is_some() {
+                return Err(error(
+                    Some(section.line),
+                    format!("`{}` is defined more than once", section.name),
+                ));
+            }
+
+            let definition = section
+                .to_definition(textures)
+                .map_err(|(line, e)| error(Some(line), e))?;
+            definitions[ty.id() as usize] = Some(definition);
+        }
+
+        let definitions = definitions
+            .into_iter()
+            .zip(BlockType::ALL.iter())
+            .map(|(definition, ty)| {
+                definition.ok_or_else(|| error(None, format!("`{}` isn't defined", ty.name())))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { definitions })
+    }
+
+    /// Returns the definition of the block type.
+    pub fn get(&self, ty: BlockType) -> &BlockDefinition {
+        &self.definitions[ty.id() as usize]
+    }
+}
+
+impl Section {
+    /// Reads the block definition out of the section's entries.
+    fn to_definition(
+        &self,
+        textures: &TextureResolver,
+    ) -> Result<BlockDefinition, (usize, String)> {
+        let mut hardness = None;
+        let mut transparency = None;
+        let (mut texture, mut top, mut side, mut bottom) = (None, None, None, None);
+        let mut drops = Vec::new();
+        let mut light_emission = 0;
+
+        let mut seen: Vec<&str> = Vec::new();
+        for (key, value, line) in &self.entries {
+            let line = *line;
+            if seen.contains(&key.as_str()) {
+                return Err((line, format!("`{}` is set more than once", key)));
+            }
+            seen.push(key);
+
+            match key.as_str() {
+                "hardness" => match value {
+                    Value::Number(n) if *n >= 0.0 => hardness = Some(*n),
+                    _ => return Err((line, "`hardness` has to be a number of at least 0".into())),
+                },
+                "transparency" => {
+                    transparency = Some(match text(key, value, line)? {
+                        "opaque" => Transparency::Opaque,
+                        "cutout" => Transparency::Cutout,
+                        "translucent" => Transparency::Translucent,
+                        "invisible" => Transparency::Invisible,
+                        other => {
+                            return Err((line, format!("unknown transparency `{}`", other)));
+                        }
+                    })
+                }
+                "texture" | "top" | "side" | "bottom" => {
+                    let name = text(key, value, line)?;
+                    let found = textures(name).ok_or_else(|| {
+                        (line, format!("there's no texture named `{}` to load", name))
+                    })?;
+                    match key.as_str() {
+                        "texture" => texture = Some(found),
+                        "top" => top = Some(found),
+                        "side" => side = Some(found),
+                        _ => bottom = Some(found),
+                    }
+                }
+                "drops" => match value {
+                    Value::List(names) => {
+                        for name in names {
+                            drops.push(find_drop(name).ok_or_else(|| {
+                                (line, format!("`{}` isn't a block or a resource", name))
+                            })?);
+                        }
+                    }
+                    _ => return Err((line, "`drops` has to be a [list]".into())),
+                },
+                "light" => match value {
+                    Value::Number(n)
+                        if n.fract() == 0.0 && *n >= 0.0 && *n <= f32::from(MAX_LIGHT) =>
+                    {
+                        light_emission = *n as u8
+                    }
+                    _ => {
+                        return Err((
+                            line,
+                            format!("`light` has to be a whole number from 0 to {}", MAX_LIGHT),
+                        ))
+                    }
+                },
+                _ => return Err((line, format!("unknown key `{}`", key))),
+            }
+        }
+
+        let missing = |key: &str| (self.line, format!("`{}` is missing `{}`", self.name, key));
+        let hardness = hardness.ok_or_else(|| missing("hardness"))?;
+        let transparency = transparency.ok_or_else(|| missing("transparency"))?;
+
+        let face_textures = if transparency == Transparency::Invisible {
+            if texture.or(top).or(side).or(bottom).is_some() {
+                return Err((
+                    self.line,
+                    format!("`{}` is invisible, so it can't have textures", self.name),
+                ));
+            }
+            None
+        } else {
+            let face = |face: Option<BlockTextureIndex>, key| {
+                face.or(texture).ok_or_else(|| {
+                    (
+                        self.line,
+                        format!("`{}` needs a `texture` or a `{}` texture", self.name, key),
+                    )
+                })
+            };
+            Some(FaceTextures {
+                top: face(top, "top")?,
+                side: face(side, "side")?,
+                bottom: face(bottom, "bottom")?,
+            })
+        };
+
+        Ok(BlockDefinition {
+            hardness,
+            transparency,
+            textures: face_textures,
+            drops,
+            light_emission,
+        })
+    }
+}
+
+/// Finds what a `block:<name>` or `resource:<name>` drop refers to.
+fn find_drop(name: &str) -> Option<BlockDrop> {
+    let colon = name.find(':')?;
+    let (kind, name) = (&name[..colon], &name[colon + 1..]);
+    match kind {
+        "block" => BlockType::from_name(name).map(BlockDrop::Block),
+        "resource" => Resource::from_name(name).map(BlockDrop::Resource),
+        _ => None,
+    }
+}
+
+/// A mistake in block definitions.
+#[derive(Debug)]
+pub struct DefinitionError {
+    path: String,
+
+    /// The line the mistake is on, if it's on one line in particular.
+    line: Option<usize>,
+    message: String,
+}
+
+impl Display for DefinitionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}, line {}: {}", self.path, line, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl Error for DefinitionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<BlockRegistry, DefinitionError> {
+        BlockRegistry::parse("blocks.defs", source, &BlockTextureIndex::from_name)
+    }
+
+    /// Returns the built-in definitions with the first `from` replaced by `to`.
+    fn changed(from: &str, to: &str) -> String {
+        assert!(
+            BUILT_IN_DEFINITIONS.contains(from),
+            "`{}` isn't there",
+            from
+        );
+        BUILT_IN_DEFINITIONS.replacen(from, to, 1)
+    }
+
+    /// Returns the line the text is on in the source, counting from 1.
+    fn line_of(source: &str, text: &str) -> usize {
+        source[..source.find(text).unwrap()].matches('\n').count() + 1
+    }
+
+    #[test]
+    fn built_in_definitions_are_complete() {
+        let registry = parse(BUILT_IN_DEFINITIONS).unwrap();
+
+        let grass = registry.get(BlockType::Grass);
+        let textures = grass.textures.unwrap();
+        assert_eq!(textures.top, BlockTextureIndex::Grass);
+        assert_eq!(textures.bottom, BlockTextureIndex::Dirt);
+        assert_eq!(grass.transparency, Transparency::Opaque);
+        assert!(registry.get(BlockType::Air).textures.is_none());
+    }
+
+    #[test]
+    fn only_lit_furnaces_give_off_light() {
+        let registry = parse(BUILT_IN_DEFINITIONS).unwrap();
+        assert_eq!(registry.get(BlockType::Furnace).light_emission, 0);
+        assert!(registry.get(BlockType::LitFurnace).light_emission > 0);
+    }
+
+    #[test]
+    fn block_types_cant_be_defined_twice() {
+        let source = format!(
+            "{}\n[sand]\nhardness = 1\ntransparency = \"opaque\"\ntexture = \"sand\"\n",
+            BUILT_IN_DEFINITIONS
+        );
+        let e = parse(&source).unwrap_err();
+
+        assert_eq!(e.line, Some(BUILT_IN_DEFINITIONS.lines().count() + 2));
+        assert!(e.message.contains("more than once"), "{}", e);
+    }
+
+    #[test]
+    fn textures_have_to_be_found() {
+        let source = changed("texture = \"sand\"", "texture = \"quicksand\"");
+        let e = parse(&source).unwrap_err();
+        assert_eq!(e.line, Some(line_of(&source, "quicksand")));
+        assert!(e.message.contains("`quicksand`"), "{}", e);
+
+        // a texture that exists can still be missing its image
+        let without_sand = |name: &str| match name {
+            "sand" => None,
+            _ => BlockTextureIndex::from_name(name),
+        };
+        let missing_image =
+            BlockRegistry::parse("blocks.defs", BUILT_IN_DEFINITIONS, &without_sand).unwrap_err();
+        assert!(
+            missing_image.message.contains("`sand`"),
+            "{}",
+            missing_image
+        );
+    }
+
+    #[test]
+    fn required_fields_have_to_be_there() {
+        let source = changed("[sand]\nhardness = 5\n", "[sand]\n");
+        let e = parse(&source).unwrap_err();
+
+        assert_eq!(e.line, Some(line_of(&source, "[sand]")));
+        assert!(e.message.contains("`hardness`"), "{}", e);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported_with_their_line() {
+        let source = changed("hardness = 5", "hardness 5");
+        let e = parse(&source).unwrap_err();
+
+        assert_eq!(e.line, Some(line_of(&source, "hardness 5")));
+        assert!(e.to_string().starts_with("blocks.defs, line "), "{}", e);
+    }
+}
@@ -1,36 +1,126 @@
+/// Indices of the two triangles of a quad, into its four vertices.
+pub const QUAD_INDICES: [u16; 6] = [0, 1, 2, 2, 1, 3];
+
+/// Number of quads that the quad index buffer has indices for, which is as many as 16-bit indices
+/// can reach.
+pub const MAX_QUADS_PER_DRAW: usize = (u16::MAX as usize + 1) / 4;
+
+/// Makes the index buffer that every chunk mesh is drawn with. Every quad in a mesh is made of
+/// four vertices in a row, so the same indices work for all of them.
+pub fn make_quad_index_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    let indices: Vec<u16> = (0..MAX_QUADS_PER_DRAW)
+        .flat_map(|quad| {
+            QUAD_INDICES
+                .iter()
+                .map(move |&index| (quad * 4) as u16 + index)
+        })
+        .collect();
+
+    device.create_buffer_with_data(bytemuck::cast_slice(&indices), wgpu::BufferUsage::INDEX)
+}
+
+/// Returns the size, in bytes, of the buffer made by `make_quad_index_buffer`.
+pub fn quad_index_buffer_size() -> wgpu::BufferAddress {
+    (MAX_QUADS_PER_DRAW * QUAD_INDICES.len() * std::mem::size_of::<u16>()) as wgpu::BufferAddress
+}
+
+/// Makes the layout of the bind group that each chunk keeps its uniforms in.
+pub fn make_chunk_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+        }],
+        label: Some("chunk bind group layout"),
+    })
+}
+
+/// Makes the pipeline that draws opaque and cutout blocks. Cutout pixels with low alpha are
+/// thrown away by the fragment shader, so this pipeline never needs to blend.
+///
 /// NOTE: Why are we passing in block_texture_bind_group_layout when we could just make it here? I
 /// think making it more than once causes inconsistencies between bind groups.
 pub fn make_chunk_render_pipeline(
     engine: &mut crate::engine::Engine,
     block_texture_bind_group_layout: &wgpu::BindGroupLayout,
     uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    chunk_bind_group_layout: &wgpu::BindGroupLayout,
+) -> Result<wgpu::RenderPipeline, Box<dyn std::error::Error>> {
+    make_pipeline(
+        engine,
+        &[
+            block_texture_bind_group_layout,
+            uniform_bind_group_layout,
+            chunk_bind_group_layout,
+        ],
+        include_str!("../shaders/block.frag"),
+        wgpu::BlendDescriptor::REPLACE,
+        wgpu::BlendDescriptor::REPLACE,
+        true,
+    )
+}
+
+/// Makes the pipeline that draws translucent blocks, like glass and water, over whatever has
+/// already been drawn. Translucent blocks are still tested against the depth buffer, so they're
+/// hidden behind opaque blocks, but don't write to it, so they don't hide each other.
+pub fn make_translucent_chunk_render_pipeline(
+    engine: &mut crate::engine::Engine,
+    block_texture_bind_group_layout: &wgpu::BindGroupLayout,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    chunk_bind_group_layout: &wgpu::BindGroupLayout,
+) -> Result<wgpu::RenderPipeline, Box<dyn std::error::Error>> {
+    make_pipeline(
+        engine,
+        &[
+            block_texture_bind_group_layout,
+            uniform_bind_group_layout,
+            chunk_bind_group_layout,
+        ],
+        include_str!("../shaders/block_translucent.frag"),
+        wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        false,
+    )
+}
+
+fn make_pipeline(
+    engine: &mut crate::engine::Engine,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    fragment_shader: &str,
+    color_blend: wgpu::BlendDescriptor,
+    alpha_blend: wgpu::BlendDescriptor,
+    depth_write: bool,
 ) -> Result<wgpu::RenderPipeline, Box<dyn std::error::Error>> {
     // describes how colors are stored and processed throughout the pipeline
     let color_states = [wgpu::ColorStateDescriptor {
         format: engine.get_swap_chain_descriptor().format,
-        color_blend: wgpu::BlendDescriptor::REPLACE,
-        alpha_blend: wgpu::BlendDescriptor::REPLACE,
+        color_blend,
+        alpha_blend,
 
         // write r, g, b, and a
         write_mask: wgpu::ColorWrite::ALL,
     }];
 
     // compile texture shaders
-    let (vs_module, fs_module) = engine.compile_shader_modules(
-        include_str!("../shaders/block.vert"),
-        include_str!("../shaders/block.frag"),
-    )?;
+    let (vs_module, fs_module) =
+        engine.compile_shader_modules(include_str!("../shaders/block.vert"), fragment_shader)?;
 
-    let render_pipeline_layout =
-        engine
-            .get_device()
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                bind_group_layouts: &[&block_texture_bind_group_layout, &uniform_bind_group_layout],
-            });
+    let render_pipeline_layout = engine
+        .get_device()
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { bind_group_layouts });
 
     let chunk_vertex_buffer_descriptors = super::ChunkMeshVertex::vertex_buffer_descriptors();
 
-    let chunk_render_pipeline_descriptor = crate::utils::make_render_pipeline_descriptor(
+    let mut chunk_render_pipeline_descriptor = crate::utils::make_render_pipeline_descriptor(
         &render_pipeline_layout,
         &vs_module,
         &fs_module,
@@ -39,6 +129,10 @@ pub fn make_chunk_render_pipeline(
         true,
     );
 
+    if let Some(depth_stencil_state) = &mut chunk_render_pipeline_descriptor.depth_stencil_state {
+        depth_stencil_state.depth_write_enabled = depth_write;
+    }
+
     Ok(engine
         .get_device()
         .create_render_pipeline(&chunk_render_pipeline_descriptor))
@@ -0,0 +1,406 @@
+use super::{Block, BlockType, Chunk, CHUNK_BLOCK_WIDTH};
+use crate::world::terrain::TerrainGenerator;
+use crate::world::ChunkCoords;
+use cgmath::Vector3;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::mem::size_of;
+
+/// Number of blocks in a chunk.
+const BLOCK_COUNT: usize = CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH;
+
+#[derive(Debug, Copy, Clone)]
+struct PaletteEntry {
+    block_type: BlockType,
+
+    /// Health of blocks of this type that have never been damaged. Worked out once, when the
+    /// entry is added, instead of every time a block is read.
+    lifespan: f32,
+
+    /// Number of blocks that use this entry. Entries that drop to zero are reused by the next new
+    /// block type instead of growing the palette.
+    count: u32,
+}
+
+/// Compact storage for the blocks of a chunk. Each distinct block type in the chunk gets an entry
+/// in a palette, and every block is stored as an index into that palette, packed into as few bits
+/// as the palette needs; a chunk of nothing but air stores no indices at all. Block state that's
+/// rarely different from the default, like health and position offsets, is kept in sparse side
+/// tables keyed by block index.
+#[derive(Debug, Clone)]
+pub struct BlockStorage {
+    palette: Vec<PaletteEntry>,
+
+    /// Bits used by each packed palette index. Zero while the palette has one entry.
+    bits_per_index: u32,
+
+    /// Packed palette indices, in `blocks[i][j][k]` order. Indices never straddle two words.
+    indices: Vec<u64>,
+
+    /// Health of blocks whose health isn't their type's lifespan.
+    health: HashMap<u16, f32>,
+
+    /// Offsets of blocks that have one.
+    position_offsets: HashMap<u16, Vector3<f32>>,
+}
+
+impl Default for BlockStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockStorage {
+    /// Makes storage full of air.
+    pub fn new() -> Self {
+        Self {
+            palette: vec![PaletteEntry {
+                block_type: BlockType::Air,
+                lifespan: Block::lifespan_of(BlockType::Air),
+                count: BLOCK_COUNT as u32,
+            }],
+            bits_per_index: 0,
+            indices: Vec::new(),
+            health: HashMap::new(),
+            position_offsets: HashMap::new(),
+        }
+    }
+
+    /// Returns the block at the array position.
+    pub fn get(&self, i: usize, j: usize, k: usize) -> Block {
+        let index = Self::block_index(i, j, k);
+        let entry = &self.palette[self.palette_index(index)];
+        let key = index as u16;
+
+        Block::new(
+            entry.block_type,
+            self.health.get(&key).copied().unwrap_or(entry.lifespan),
+            self.position_offsets.get(&key).copied(),
+        )
+    }
+
+    /// Returns the type of the block at the array position. Cheaper than `get` when the rest of
+    /// the block isn't needed.
+    pub fn block_type(&self, i: usize, j: usize, k: usize) -> BlockType {
+        self.palette[self.palette_index(Self::block_index(i, j, k))].block_type
+    }
+
+    /// Sets the block at the array position.
+    pub fn set(&mut self, i: usize, j: usize, k: usize, block: Block) {
+        let index = Self::block_index(i, j, k);
+        let old_palette_index = self.palette_index(index);
+
+        if self.palette[old_palette_index].block_type != block.block_type() {
+            let new_palette_index = self.palette_entry_for(block.block_type());
+            self.palette[old_palette_index].count -= 1;
+            self.palette[new_palette_index].count += 1;
+            self.set_palette_index(index, new_palette_index);
+        }
+
+        // floats are compared by their bits so that whatever was set is exactly what's read back
+        let key = index as u16;
+        let lifespan = self.palette[self.palette_index(index)].lifespan;
+        if block.health().to_bits() == lifespan.to_bits() {
+            self.health.remove(&key);
+        } else {
+            self.health.insert(key, block.health());
+        }
+
+        match block.position_offset() {
+            Some(offset) => self.position_offsets.insert(key, offset),
+            None => self.position_offsets.remove(&key),
+        };
+    }
+
+    /// Returns the number of distinct block types in use.
+    pub fn block_type_count(&self) -> usize {
+        self.palette.iter().filter(|e| e.count > 0).count()
+    }
+
+    /// Returns every block type in use.
+    pub fn block_types(&self) -> impl Iterator<Item = BlockType> + '_ {
+        self.palette
+            .iter()
+            .filter(|e| e.count > 0)
+            .map(|e| e.block_type)
+    }
+
+    /// Returns roughly how many bytes the storage takes up, including everything it allocated.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.palette.capacity() * size_of::<PaletteEntry>()
+            + self.indices.capacity() * size_of::<u64>()
+            + self.health.capacity() * (size_of::<u16>() + size_of::<f32>())
+            + self.position_offsets.capacity() * (size_of::<u16>() + size_of::<Vector3<f32>>())
+    }
+
+    fn block_index(i: usize, j: usize, k: usize) -> usize {
+        (i * CHUNK_BLOCK_WIDTH + j) * CHUNK_BLOCK_WIDTH + k
+    }
+
+    fn palette_index(&self, index: usize) -> usize {
+        if self.bits_per_index == 0 {
+            return 0;
+        }
+
+        let per_word = 64 / self.bits_per_index as usize;
+        let shift = (index % per_word) as u32 * self.bits_per_index;
+        let mask = (1u64 << self.bits_per_index) - 1;
+        ((self.indices[index / per_word] >> shift) & mask) as usize
+    }
+
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        let per_word = 64 / self.bits_per_index as usize;
+        let shift = (index % per_word) as u32 * self.bits_per_index;
+        let mask = (1u64 << self.bits_per_index) - 1;
+
+        let word = &mut self.indices[index / per_word];
+        *word = (*word & !(mask << shift)) | ((palette_index as u64) << shift);
+    }
+
+    /// Returns the index of the palette entry for the block type, adding one if there isn't one.
+    /// The entry's count isn't changed.
+    fn palette_entry_for(&mut self, block_type: BlockType) -> usize {
+        if let Some(index) = self.palette.iter().position(|e| e.block_type == block_type) {
+            return index;
+        }
+
+        let entry = PaletteEntry {
+            block_type,
+            lifespan: Block::lifespan_of(block_type),
+            count: 0,
+        };
+
+        if let Some(index) = self.palette.iter().position(|e| e.count == 0) {
+            self.palette[index] = entry;
+            return index;
+        }
+
+        self.palette.push(entry);
+        if self.palette.len() > 1 << self.bits_per_index {
+            self.repack(self.bits_per_index + 1);
+        }
+        self.palette.len() - 1
+    }
+
+    /// Packs every palette index again using a different number of bits.
+    fn repack(&mut self, bits_per_index: u32) {
+        let old_indices: Vec<usize> = (0..BLOCK_COUNT).map(|i| self.palette_index(i)).collect();
+
+        let per_word = 64 / bits_per_index as usize;
+        self.bits_per_index = bits_per_index;
+        self.indices = vec![0; BLOCK_COUNT.div_ceil(per_word)];
+
+        for (index, palette_index) in old_indices.into_iter().enumerate() {
+            self.set_palette_index(index, palette_index);
+        }
+    }
+}
+
+/// Size, in bytes, of the blocks of a chunk stored the old way: a plain array of every `Block`.
+pub const UNPACKED_CHUNK_SIZE: usize =
+    size_of::<[[[Block; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH]>();
+
+/// Memory used by the block storage of generated chunks, compared to storing every block in a
+/// plain array.
+#[derive(Debug, Default)]
+pub struct MemoryStatistics {
+    chunk_count: usize,
+    total_bytes: usize,
+    smallest_bytes: usize,
+    largest_bytes: usize,
+
+    /// Number of chunks for each count of distinct block types.
+    block_type_counts: HashMap<usize, usize>,
+}
+
+impl MemoryStatistics {
+    /// Generates every chunk between `from` and `to` (inclusive) and measures their storage.
+    pub fn gather(generator: &TerrainGenerator, from: ChunkCoords, to: ChunkCoords) -> Self {
+        let mut stats = Self {
+            smallest_bytes: usize::MAX,
+            ..Default::default()
+        };
+
+        for chunk_i in from.0..=to.0 {
+            for chunk_j in from.1..=to.1 {
+                for chunk_k in from.2..=to.2 {
+                    let (chunk, _) = Chunk::generate(chunk_i, chunk_j, chunk_k, generator);
+                    let storage = chunk.storage();
+                    let bytes = storage.memory_usage();
+
+                    stats.chunk_count += 1;
+                    stats.total_bytes += bytes;
+                    stats.smallest_bytes = stats.smallest_bytes.min(bytes);
+                    stats.largest_bytes = stats.largest_bytes.max(bytes);
+                    *stats
+                        .block_type_counts
+                        .entry(storage.block_type_count())
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+
+        stats
+    }
+}
+
+impl Display for MemoryStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let chunk_count = self.chunk_count.max(1);
+        let average_bytes = self.total_bytes / chunk_count;
+
+        writeln!(f, "{} chunks", self.chunk_count)?;
+        writeln!(
+            f,
+            "{:<10} {:>14} {:>14}",
+            "layout", "bytes/chunk", "total bytes"
+        )?;
+        writeln!(
+            f,
+            "{:<10} {:>14} {:>14}",
+            "array",
+            UNPACKED_CHUNK_SIZE,
+            UNPACKED_CHUNK_SIZE * self.chunk_count
+        )?;
+        writeln!(
+            f,
+            "{:<10} {:>14} {:>14}",
+            "palette", average_bytes, self.total_bytes
+        )?;
+        writeln!(
+            f,
+            "palette chunks use {} to {} bytes, {:.1}x smaller on average",
+            self.smallest_bytes,
+            self.largest_bytes,
+            UNPACKED_CHUNK_SIZE as f32 / average_bytes.max(1) as f32
+        )?;
+
+        let mut type_counts: Vec<_> = self.block_type_counts.iter().collect();
+        type_counts.sort();
+        for (types, chunks) in type_counts {
+            writeln!(f, "{:>4} chunks with {} block types", chunks, types)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the array position of the nth block in a scattered but fixed order, so blocks set
+    /// one after another land in different words of the packed indices.
+    fn scattered(n: usize) -> (usize, usize, usize) {
+        let index = n * 7919 % BLOCK_COUNT;
+        let width = CHUNK_BLOCK_WIDTH;
+        (
+            index / (width * width),
+            index / width % width,
+            index % width,
+        )
+    }
+
+    /// The bits each packed index needs for a palette with this many entries.
+    fn bits_for(palette_len: usize) -> u32 {
+        match palette_len {
+            1 => 0,
+            n => usize::BITS - (n - 1).leading_zeros(),
+        }
+    }
+
+    #[test]
+    fn blocks_round_trip_through_every_index_width() {
+        const BLOCKS_PER_TYPE: usize = 5;
+        let mut storage = BlockStorage::new();
+        let mut widths = vec![storage.bits_per_index];
+
+        for (t, &block_type) in BlockType::ALL.iter().enumerate().skip(1) {
+            for n in t * BLOCKS_PER_TYPE..(t + 1) * BLOCKS_PER_TYPE {
+                let (i, j, k) = scattered(n);
+                storage.set(i, j, k, Block::from(block_type));
+            }
+            assert_eq!(storage.bits_per_index, bits_for(t + 1));
+            widths.push(storage.bits_per_index);
+
+            // every block set so far, through every repack, still reads back
+            for n in 0..BLOCK_COUNT {
+                let (i, j, k) = scattered(n);
+                let expected = match n / BLOCKS_PER_TYPE {
+                    owner if (1..=t).contains(&owner) => BlockType::ALL[owner],
+                    _ => BlockType::Air,
+                };
+                assert_eq!(storage.block_type(i, j, k), expected, "block {}", n);
+            }
+        }
+
+        widths.dedup();
+        assert_eq!(widths, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(storage.block_type_count(), BlockType::ALL.len());
+    }
+
+    #[test]
+    fn side_tables_survive_a_repack() {
+        let offset = Vector3::new(0.125, -0.25, 0.5);
+        let mut storage = BlockStorage::new();
+        storage.set(3, 4, 5, Block::new(BlockType::Stone, 0.25, Some(offset)));
+
+        // push the palette from one bit per index to three
+        for (n, &block_type) in BlockType::ALL[5..10].iter().enumerate() {
+            storage.set(n, 0, 0, Block::from(block_type));
+        }
+        assert_eq!(storage.bits_per_index, 3);
+
+        let block = storage.get(3, 4, 5);
+        assert_eq!(block.block_type(), BlockType::Stone);
+        assert_eq!(block.health(), 0.25);
+        assert_eq!(block.position_offset(), Some(offset));
+    }
+
+    #[test]
+    fn side_tables_are_cleared_when_a_block_is_replaced() {
+        let offset = Vector3::new(0.125, -0.25, 0.5);
+        let mut storage = BlockStorage::new();
+        storage.set(3, 4, 5, Block::new(BlockType::Stone, 0.25, Some(offset)));
+        storage.set(3, 4, 5, Block::from(BlockType::Dirt));
+
+        let block = storage.get(3, 4, 5);
+        assert_eq!(block.health(), Block::lifespan_of(BlockType::Dirt));
+        assert_eq!(block.position_offset(), None);
+        assert!(storage.health.is_empty());
+        assert!(storage.position_offsets.is_empty());
+    }
+
+    #[test]
+    fn emptied_palette_entries_are_reused() {
+        let mut storage = BlockStorage::new();
+        storage.set(0, 0, 0, Block::from(BlockType::Stone));
+        storage.set(1, 0, 0, Block::from(BlockType::Dirt));
+        assert_eq!(storage.palette.len(), 3);
+        assert_eq!(storage.bits_per_index, 2);
+
+        // once the last stone is gone, sand takes over its entry instead of adding one
+        storage.set(0, 0, 0, Block::from(BlockType::Air));
+        assert_eq!(storage.block_type_count(), 2);
+        storage.set(2, 0, 0, Block::from(BlockType::Sand));
+
+        assert_eq!(storage.palette.len(), 3);
+        assert_eq!(storage.bits_per_index, 2);
+        assert_eq!(storage.block_type(0, 0, 0), BlockType::Air);
+        assert_eq!(storage.block_type(1, 0, 0), BlockType::Dirt);
+        assert_eq!(storage.block_type(2, 0, 0), BlockType::Sand);
+    }
+
+    #[test]
+    fn palettes_take_less_memory_than_arrays() {
+        assert!(BlockStorage::new().memory_usage() * 100 < UNPACKED_CHUNK_SIZE);
+
+        let mut storage = BlockStorage::new();
+        for n in 0..BLOCK_COUNT {
+            let (i, j, k) = scattered(n);
+            storage.set(i, j, k, Block::from(BlockType::ALL[n % 4]));
+        }
+        assert!(storage.memory_usage() * 8 < UNPACKED_CHUNK_SIZE);
+    }
+}
@@ -34,6 +34,11 @@ impl Camera {
         &self.matrix
     }
 
+    /// Returns the position of the camera's eye.
+    pub fn position(&self) -> cgmath::Point3<f32> {
+        self.eye
+    }
+
     pub fn jump_position_to(&mut self, position: (f32, f32, f32)) {
         self.eye = position.into();
         self.update_view_projection_matrix()
@@ -1,10 +1,19 @@
+use crate::blocks::Block;
 use crate::characters::{Character, CharacterAction, Direction, FacingDirection};
 use crate::items;
 use crate::items::{Backpack, Item, ItemStack};
 use crate::maths::AABB;
 use crate::physics::PhysicalObject;
-use crate::traits::{Killable, Logicable, Renderable};
-use cgmath::Vector3;
+use crate::sprite::{self, Sprite};
+use crate::traits::{Killable, Logicable, Persist, Renderable};
+use crate::world::save::{Reader, SaveError};
+use cgmath::{Matrix4, Point3, Rad, Vector3};
+use std::error::Error;
+use std::fmt;
+
+/// How big a pixel of the chicken's sprites is in the world. It's the same as a pixel of a block
+/// texture, so the chicken looks like it belongs with the blocks.
+const PIXEL_SIZE: f32 = Block::WIDTH / 16.0;
 
 /// The main character of this game. we ain't callin it chicky chicky for nothing folks
 #[derive(Debug)]
@@ -15,29 +24,147 @@ pub struct Chicken {
     facing: FacingDirection,
     health: f32,
     lifespan: f32,
-    // chicken_sprites: HashMap<CharacterAction, Sprite>,
+
+    /// None until the sprites are given to the chicken, which can't be drawn until then.
+    sprites: Option<ChickenSprites>,
 }
 
 impl Chicken {
     /// Creates and initializes a new Chicken
     pub fn new() -> Self {
-        // let c: Self = Default::default();
-
-        // c.chicken_sprites[CharacterAction::Nothing] =
-        //     Sprite::must_new("assets/photos/chicken/stand.png", 0, 0);
-        // c.chicken_sprites[CharacterAction::Run] =
-        //     Sprite::must_new("assets/photos/chicken/sprint.png", 4, 0.15);
-        // c.chicken_sprites[CharacterAction::Walk] =
-        //     Sprite::must_new("assets/photos/chicken/walk.png", 4, 0.2);
-        // c.chicken_sprites[CharacterAction::Squat] =
-        //     Sprite::must_new("assets/photos/chicken/squat.png", 0, 0);
-        // c.chicken_sprites[CharacterAction::Push] =
-        //     Sprite::must_new("assets/photos/chicken/push.png", 4, 0.75);
-        // c.chicken_sprites[CharacterAction::Fall] =
-        //     Sprite::must_new("assets/photos/chicken/fall.png", 2, 0.1);
-
         Default::default()
     }
+
+    /// Gives the chicken the sprites it's drawn with.
+    pub fn set_sprites(&mut self, sprites: ChickenSprites) {
+        self.sprites = Some(sprites);
+    }
+
+    /// Starts doing something else. The sprite sheet for the new action starts from its first
+    /// frame.
+    fn set_action(&mut self, action: CharacterAction) {
+        if action == self.action {
+            return;
+        }
+
+        self.action = action;
+        if let Some(sprites) = &mut self.sprites {
+            sprites.for_action_mut(action).animation_mut().reset();
+        }
+    }
+
+    /// Moves the chicken's sprite to where the chicken is and turns it to face the camera at
+    /// `eye`, then copies it to the GPU. It's only turned around the vertical axis so that the
+    /// chicken always stands up straight, and it's mirrored when the chicken faces left.
+    pub fn update_sprite(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        eye: Point3<f32>,
+    ) {
+        let position = self.physical.position();
+        let facing = self.facing;
+        let sprite = match &mut self.sprites {
+            Some(sprites) => sprites.for_action_mut(self.action),
+            None => return,
+        };
+
+        // the sheets are drawn facing right
+        let flip = match facing {
+            FacingDirection::Right => 1.0,
+            FacingDirection::Left => -1.0,
+        };
+        sprite.set_size(
+            flip * sprite.get_pixel_width() as f32 * PIXEL_SIZE,
+            sprite.get_pixel_height() as f32 * PIXEL_SIZE,
+        );
+
+        let angle = Rad((eye.x - position.x).atan2(eye.z - position.z));
+        sprite.set_rotation(Matrix4::from_angle_y(angle));
+        sprite.set_position(position.x, position.y, position.z);
+
+        sprite.update_uniforms(device, queue);
+    }
+}
+
+/// The chicken's sprite sheets, one for each thing it can be seen doing.
+pub struct ChickenSprites {
+    stand: Sprite,
+    walk: Sprite,
+    sprint: Sprite,
+    squat: Sprite,
+    push: Sprite,
+    fall: Sprite,
+
+    /// The quad every sheet is drawn on.
+    quad: wgpu::Buffer,
+}
+
+impl ChickenSprites {
+    /// Where the sheets are, relative to the working directory.
+    const DIRECTORY: &'static str = "assets/images/chicken";
+
+    /// Loads every sheet. The command buffers copy them to the GPU.
+    pub fn load(
+        device: &wgpu::Device,
+        sprite_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<(Self, Vec<wgpu::CommandBuffer>), Box<dyn Error>> {
+        let mut commands = Vec::new();
+        let mut load = |name: &str, frames, seconds_per_frame| {
+            let path = std::path::Path::new(Self::DIRECTORY).join(name);
+            let (sprite, command_buffer) = Sprite::load(
+                device,
+                sprite_bind_group_layout,
+                path,
+                frames,
+                seconds_per_frame,
+            )?;
+            commands.push(command_buffer);
+            Ok::<_, Box<dyn Error>>(sprite)
+        };
+
+        let sprites = Self {
+            stand: load("stand.png", 1, 0.0)?,
+            walk: load("walk.png", 4, 0.2)?,
+            sprint: load("sprint.png", 4, 0.15)?,
+            squat: load("squat.png", 1, 0.0)?,
+            push: load("push.png", 4, 0.75)?,
+            fall: load("fall.png", 2, 0.1)?,
+            quad: sprite::make_quad_vertex_buffer(device),
+        };
+
+        Ok((sprites, commands))
+    }
+
+    /// Returns the sheet for the action. Actions without a sheet of their own show the chicken
+    /// standing.
+    fn for_action(&self, action: CharacterAction) -> &Sprite {
+        match action {
+            CharacterAction::Walk => &self.walk,
+            CharacterAction::Run => &self.sprint,
+            CharacterAction::Squat => &self.squat,
+            CharacterAction::Push => &self.push,
+            CharacterAction::Fall => &self.fall,
+            _ => &self.stand,
+        }
+    }
+
+    fn for_action_mut(&mut self, action: CharacterAction) -> &mut Sprite {
+        match action {
+            CharacterAction::Walk => &mut self.walk,
+            CharacterAction::Run => &mut self.sprint,
+            CharacterAction::Squat => &mut self.squat,
+            CharacterAction::Push => &mut self.push,
+            CharacterAction::Fall => &mut self.fall,
+            _ => &mut self.stand,
+        }
+    }
+}
+
+impl fmt::Debug for ChickenSprites {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ChickenSprites")
+    }
 }
 
 impl Default for Chicken {
@@ -55,19 +182,68 @@ impl Default for Chicken {
             facing: Default::default(),
             health: Default::default(),
             lifespan: Default::default(),
+            sprites: None,
         }
     }
 }
 
+/// Saves everything about the chicken except what it's doing at the moment, which it stops doing
+/// when loaded.
+impl Persist for Chicken {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        self.physical.write_to(bytes);
+        self.facing.write_to(bytes);
+        bytes.extend_from_slice(&self.health.to_le_bytes());
+        bytes.extend_from_slice(&self.lifespan.to_le_bytes());
+
+        bytes.extend_from_slice(&(self.backpack.len() as u32).to_le_bytes());
+        for (item, count) in &self.backpack {
+            item.write_to(bytes);
+            bytes.push(*count);
+        }
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        let physical = PhysicalObject::read_from(reader)?;
+        let facing = FacingDirection::read_from(reader)?;
+        let health = reader.read_f32()?;
+        let lifespan = reader.read_f32()?;
+
+        let stack_count = reader.read_u32()?;
+        let mut backpack = Backpack::new();
+        for _ in 0..stack_count {
+            backpack.push((Item::read_from(reader)?, reader.read_u8()?));
+        }
+
+        Ok(Self {
+            physical,
+            backpack,
+            action: Default::default(),
+            facing,
+            health,
+            lifespan,
+            sprites: None,
+        })
+    }
+}
+
 impl Logicable for Chicken {
-    fn logic(&mut self, _delta_sec: f32) {
-        todo!()
+    /// Animates the chicken's sprite.
+    fn logic(&mut self, delta_sec: f32) {
+        if let Some(sprites) = &mut self.sprites {
+            sprites.for_action_mut(self.action).animate(delta_sec);
+        }
     }
 }
 
 impl Renderable for Chicken {
-    fn render(&self, _: &wgpu::RenderPass) {
-        todo!()
+    /// Draws the chicken's sprite. The sprite pipeline has to be bound already.
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(sprites) = &self.sprites {
+            sprites
+                .for_action(self.action)
+                .render(render_pass, &sprites.quad);
+        }
     }
 }
 
@@ -75,9 +251,9 @@ impl Character for Chicken {
     /// Walks the chicken
     fn walk(&mut self, direction: Direction, sup: bool) {
         if sup {
-            self.action = CharacterAction::Run;
+            self.set_action(CharacterAction::Run);
         } else {
-            self.action = CharacterAction::Walk;
+            self.set_action(CharacterAction::Walk);
         }
 
         match direction {
@@ -95,12 +271,12 @@ impl Character for Chicken {
     /// Squats the chicken
     fn down(&mut self, _sup: bool) {
         self.stop();
-        self.action = CharacterAction::Squat;
+        self.set_action(CharacterAction::Squat);
     }
 
     /// Stops the chicken's movement
     fn stop(&mut self) {
-        self.action = CharacterAction::Nothing;
+        self.set_action(CharacterAction::Nothing);
     }
 
     fn attack<K: Killable>(&self, _with: Option<&items::Item>, _power: f32, _who: K) {}
@@ -132,3 +308,43 @@ impl Killable for Chicken {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_chicken(chicken: &Chicken) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        chicken.write_to(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn chickens_round_trip_with_their_backpacks() {
+        let mut chicken = Chicken::new();
+        chicken.backpack = items::backpack_of_every_kind();
+        chicken.facing = FacingDirection::Left;
+        chicken.health = 3.5;
+        chicken.lifespan = 10.0;
+
+        let bytes = write_chicken(&chicken);
+        let read = Chicken::read_from(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", chicken));
+    }
+
+    #[test]
+    fn unknown_items_in_the_backpack_are_corrupt() {
+        let mut chicken = Chicken::new();
+        chicken.backpack = vec![(Item::Other, 1)];
+
+        // the backpack ends with the item's kind and then the size of its stack
+        let mut bytes = write_chicken(&chicken);
+        let kind = bytes.len() - 2;
+        bytes[kind] = 200;
+
+        match Chicken::read_from(&mut Reader::new(&bytes)) {
+            Err(SaveError::Corrupt(_)) => {}
+            other => panic!("read as {:?}", other),
+        }
+    }
+}
@@ -6,6 +6,7 @@ pub use chicken::*;
 
 use crate::items;
 use crate::traits::*;
+use crate::world::save::{Reader, SaveError};
 
 pub enum Direction {
     Up,
@@ -32,7 +33,7 @@ pub trait Character: Killable + Logicable + Renderable {
 }
 
 /// Specifies what a certain character is doing.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CharacterAction {
     Nothing,
     Walk,
@@ -55,7 +56,7 @@ impl Default for CharacterAction {
 }
 
 /// Right or Left, telling which direction a character (or whatever sprite) is facing
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub enum FacingDirection {
     Right,
     Left,
@@ -66,3 +67,22 @@ impl Default for FacingDirection {
         Self::Right
     }
 }
+
+impl Persist for FacingDirection {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => FacingDirection::Right,
+            1 => FacingDirection::Left,
+            id => {
+                return Err(SaveError::Corrupt(format!(
+                    "unknown facing direction {}",
+                    id
+                )))
+            }
+        })
+    }
+}
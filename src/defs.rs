@@ -0,0 +1,198 @@
+//! Reads the game's definition files, like `assets/blocks.defs` and resource pack manifests. The
+//! format looks a bit like TOML but is much smaller: `[sections]`, `# comments` and `key = value`
+//! lines whose values are numbers, "strings" or ["lists", "of strings"], each on one line. Strings
+//! have no escapes, so they can't hold a `"`.
+
+/// A value on the right side of a `key = value` line.
+#[derive(Debug)]
+pub enum Value {
+    Number(f32),
+    Text(String),
+    List(Vec<String>),
+}
+
+/// A `[name]` section and the `key = value` lines under it, along with the line numbers they
+/// were on.
+pub struct Section {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<(String, Value, usize)>,
+}
+
+/// Splits the source into sections. Errors come with the line number they were found on.
+pub fn parse_sections(source: &str) -> Result<Vec<Section>, (usize, String)> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push(Section {
+                name: line[1..line.len() - 1].trim().to_string(),
+                line: number,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let equals = line
+            .find('=')
+            .ok_or_else(|| (number, format!("expected `key = value`, found `{}`", line)))?;
+        let key = line[..equals].trim().to_string();
+        let value = parse_value(line[equals + 1..].trim()).map_err(|e| (number, e))?;
+
+        match sections.last_mut() {
+            Some(section) => section.entries.push((key, value, number)),
+            None => return Err((number, format!("`{}` isn't in a section", key))),
+        }
+    }
+
+    Ok(sections)
+}
+
+/// Cuts a comment off the end of a line, leaving any `#` inside of a string alone.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if text.starts_with('[') && text.ends_with(']') {
+        let items = text[1..text.len() - 1].trim();
+        if items.is_empty() {
+            return Ok(Value::List(Vec::new()));
+        }
+
+        return items
+            .split(',')
+            .map(|item| parse_string(item.trim()))
+            .collect::<Result<_, _>>()
+            .map(Value::List);
+    }
+
+    if text.starts_with('"') {
+        return parse_string(text).map(Value::Text);
+    }
+
+    text.parse()
+        .map(Value::Number)
+        .map_err(|_| format!("`{}` isn't a number, a \"string\" or a [list]", text))
+}
+
+fn parse_string(text: &str) -> Result<String, String> {
+    let inside = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .filter(|inside| !inside.contains('"'));
+
+    match inside {
+        Some(inside) => Ok(inside.to_string()),
+        None => Err(format!("`{}` isn't a \"string\"", text)),
+    }
+}
+
+/// Returns the value as text, or an error if it isn't a string.
+pub fn text<'a>(key: &str, value: &'a Value, line: usize) -> Result<&'a str, (usize, String)> {
+    match value {
+        Value::Text(text) => Ok(text),
+        _ => Err((line, format!("`{}` has to be a \"string\"", key))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(section: &Section) -> Vec<(&str, String, usize)> {
+        section
+            .entries
+            .iter()
+            .map(|(key, value, line)| (key.as_str(), format!("{:?}", value), *line))
+            .collect()
+    }
+
+    #[test]
+    fn sections_keep_their_entries_and_line_numbers() {
+        let source = "# a comment\n\
+                      [first]\n\
+                      number = 2.5\n\
+                      \n\
+                      text = \"a # not a comment\" # a comment\n\
+                      [ second ]\n\
+                      list = [\"a\", \"b\"]\n\
+                      empty = []\n";
+        let sections = parse_sections(source).unwrap();
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!((sections[0].name.as_str(), sections[0].line), ("first", 2));
+        assert_eq!(
+            entries(&sections[0]),
+            [
+                ("number", "Number(2.5)".to_string(), 3),
+                ("text", "Text(\"a # not a comment\")".to_string(), 5),
+            ]
+        );
+        assert_eq!((sections[1].name.as_str(), sections[1].line), ("second", 6));
+        assert_eq!(
+            entries(&sections[1]),
+            [
+                ("list", "List([\"a\", \"b\"])".to_string(), 7),
+                ("empty", "List([])".to_string(), 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        let cases = [
+            ("[a]\nnot a key value line", 2, "expected `key = value`"),
+            ("key = 1", 1, "`key` isn't in a section"),
+            ("[a]\n\nkey = word", 3, "`word` isn't a number"),
+            ("[a]\nkey = \"unclosed", 2, "isn't a \"string\""),
+            ("[a]\nkey = [\"a\", b]", 2, "`b` isn't a \"string\""),
+        ];
+
+        for (source, line, message) in cases.iter() {
+            let (found_line, found_message) = parse_sections(source).err().unwrap();
+            assert_eq!(found_line, *line, "{}", source);
+            assert!(found_message.contains(message), "{}", found_message);
+        }
+    }
+
+    #[test]
+    fn toml_that_isnt_understood_is_rejected_rather_than_misread() {
+        let sources = [
+            "[a]\nkey = \"\"\"multi-line\"\"\"",
+            "[a]\nkey = \"an \\\"escaped\\\" quote\"",
+            "[a]\nkey = { inline = 1 }",
+            "[a]\nkey = true",
+            "[a]\nkey = [1, 2]",
+        ];
+
+        for source in sources.iter() {
+            assert!(parse_sections(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn text_only_accepts_strings() {
+        let value = Value::Text("stone".to_string());
+        assert_eq!(text("texture", &value, 4).unwrap(), "stone");
+
+        let (line, message) = text("texture", &Value::Number(1.0), 4).unwrap_err();
+        assert_eq!(line, 4);
+        assert_eq!(message, "`texture` has to be a \"string\"");
+    }
+}
@@ -0,0 +1,157 @@
+use super::errors::TextureError;
+use super::mipmap::{self, MipFilter};
+
+/// A stack of 2D textures that are all the same size, sampled by layer. Unlike a 3D texture,
+/// sampling never blends between neighboring layers.
+pub struct TextureArray {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+
+    layers: u32,
+    label: Option<String>,
+    mip_filter: MipFilter,
+
+    /// The size of a single layer.
+    size: wgpu::Extent3d,
+}
+
+impl TextureArray {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// Makes an empty texture array with layers of the size. Every layer gets a full chain of mip
+    /// levels, made with the filter whenever the layer is set.
+    pub fn new(
+        device: &wgpu::Device,
+        px_dimensions: (u32, u32),
+        layers: u32,
+        mip_filter: MipFilter,
+        label: Option<&str>,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: px_dimensions.0,
+            height: px_dimensions.1,
+            depth: 1,
+        };
+        let mip_level_count = mipmap::mip_level_count(px_dimensions);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            array_layer_count: layers,
+            mip_level_count,
+            sample_count: 1,
+
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        // TextureView: offers us a *view* into our texture. the default view of a texture with
+        // layers would only show the first one, so this view covers all of them.
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Self::FORMAT,
+            dimension: wgpu::TextureViewDimension::D2Array,
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            level_count: mip_level_count,
+            base_array_layer: 0,
+            array_layer_count: layers,
+        });
+
+        // Sampler: controls how the Texture is *sampled*.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            // block faces bigger than a block repeat their texture
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Always,
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            size,
+            layers,
+            label: label.map(String::from),
+            mip_filter,
+        }
+    }
+
+    /// Returns the number of layers in the texture.
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn set_layer_from_bytes(
+        &mut self,
+        device: &wgpu::Device,
+        index: u32,
+        bytes: &[u8],
+    ) -> Result<wgpu::CommandBuffer, TextureError> {
+        self.check_index(index)?;
+
+        let img = image::load_from_memory(bytes).map_err(|e| {
+            TextureError::from_error_with_detail(e, "setting layer from bytes (loading image)")
+        })?;
+
+        self.set_layer_from_image(device, index, img)
+    }
+
+    /// Copies the image and the mip levels made from it into a layer. The image has to be exactly
+    /// the size of a layer.
+    pub fn set_layer_from_image(
+        &mut self,
+        device: &wgpu::Device,
+        index: u32,
+        img: image::DynamicImage,
+    ) -> Result<wgpu::CommandBuffer, TextureError> {
+        self.check_index(index)?;
+
+        let rgba = img.into_rgba();
+        let dimensions = rgba.dimensions();
+        if dimensions != (self.size.width, self.size.height) {
+            return Err(TextureError::from_message(format!(
+                "image for layer {} of texture array `{}` is {}x{}, but its layers are {}x{}",
+                index,
+                self.label(),
+                dimensions.0,
+                dimensions.1,
+                self.size.width,
+                self.size.height,
+            )));
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture command encoder"),
+        });
+
+        let chain = mipmap::mip_chain(rgba, self.mip_filter);
+        mipmap::copy_mip_chain(device, &mut encoder, &self.texture, index, &chain);
+
+        Ok(encoder.finish())
+    }
+
+    fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or("(no label)")
+    }
+
+    fn check_index(&self, index: u32) -> Result<(), TextureError> {
+        if index >= self.layers {
+            Err(TextureError::from_message(format!(
+                "tried to set a texture out of range: index {} on texture array `{}`, which has {} layers",
+                index,
+                self.label(),
+                self.layers
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use image::{Rgba, RgbaImage};
+
+/// How each mip level is made out of the one above it. Both keep pixel art looking like pixel art
+/// instead of blurring it the way a smoother filter would.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Every pixel is the top left of the 2×2 pixels it covers, so levels only ever have colors
+    /// from the original image.
+    Nearest,
+
+    /// Every pixel is the average of the 2×2 pixels it covers. Colors are weighted by alpha, so
+    /// the colors of fully transparent pixels never bleed into the ones next to them.
+    Box,
+}
+
+/// Returns how many mip levels an image of the size has, counting the full size one, down to a
+/// level that's a single pixel.
+pub fn mip_level_count((width, height): (u32, u32)) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Makes the next mip level of the image, which is half its size, rounding down. Neither side
+/// goes below a pixel. An odd row or column at the end of the image is left out.
+pub fn downsample(image: &RgbaImage, filter: MipFilter) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+
+    RgbaImage::from_fn(next_width, next_height, |x, y| {
+        let (left, top) = (2 * x, 2 * y);
+        match filter {
+            MipFilter::Nearest => *image.get_pixel(left, top),
+            MipFilter::Box => {
+                let (right, bottom) = ((left + 1).min(width - 1), (top + 1).min(height - 1));
+                average(&[
+                    image.get_pixel(left, top),
+                    image.get_pixel(right, top),
+                    image.get_pixel(left, bottom),
+                    image.get_pixel(right, bottom),
+                ])
+            }
+        }
+    })
+}
+
+/// Returns the image followed by every mip level below it, down to a single pixel.
+pub fn mip_chain(image: RgbaImage, filter: MipFilter) -> Vec<RgbaImage> {
+    let mut chain = Vec::with_capacity(mip_level_count(image.dimensions()) as usize);
+    chain.push(image);
+
+    while let Some(level) = chain.last().filter(|level| level.dimensions() != (1, 1)) {
+        let next = downsample(level, filter);
+        chain.push(next);
+    }
+
+    chain
+}
+
+/// Records copying every level of a mip chain into a layer of the texture.
+pub(super) fn copy_mip_chain(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    array_layer: u32,
+    chain: &[RgbaImage],
+) {
+    for (mip_level, level) in chain.iter().enumerate() {
+        let (width, height) = level.dimensions();
+
+        // COPY_SRC: copy from this buffer
+        let buffer = device
+            .create_buffer_with_data(bytemuck::cast_slice(level), wgpu::BufferUsage::COPY_SRC);
+
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                bytes_per_row: 4 * width,
+                rows_per_image: height,
+            },
+            wgpu::TextureCopyView {
+                texture,
+                mip_level: mip_level as u32,
+                array_layer,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+    }
+}
+
+fn average(pixels: &[&Rgba<u8>]) -> Rgba<u8> {
+    let count = pixels.len() as u32;
+    let total_alpha: u32 = pixels.iter().map(|p| u32::from(p[3])).sum();
+
+    let mut color = [0; 4];
+    for (channel, value) in color.iter_mut().enumerate().take(3) {
+        // with nothing to weigh them by, fully transparent pixels count equally
+        let (sum, weight) = if total_alpha == 0 {
+            let sum: u32 = pixels.iter().map(|p| u32::from(p[channel])).sum();
+            (sum, count)
+        } else {
+            let sum: u32 = pixels
+                .iter()
+                .map(|p| u32::from(p[channel]) * u32::from(p[3]))
+                .sum();
+            (sum, total_alpha)
+        };
+        *value = ((sum + weight / 2) / weight) as u8;
+    }
+    color[3] = ((total_alpha + count / 2) / count) as u8;
+
+    Rgba(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const CLEAR: Rgba<u8> = Rgba([0, 255, 0, 0]);
+
+    fn image_of(width: u32, height: u32, pixels: &[Rgba<u8>]) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| pixels[(y * width + x) as usize])
+    }
+
+    #[test]
+    fn levels_go_down_to_a_single_pixel() {
+        assert_eq!(mip_level_count((1, 1)), 1);
+        assert_eq!(mip_level_count((2, 1)), 2);
+        assert_eq!(mip_level_count((16, 16)), 5);
+        assert_eq!(mip_level_count((24, 13)), 5);
+
+        let chain = mip_chain(RgbaImage::new(16, 4), MipFilter::Box);
+        let sizes: Vec<_> = chain.iter().map(RgbaImage::dimensions).collect();
+        assert_eq!(sizes, [(16, 4), (8, 2), (4, 1), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn nearest_keeps_the_top_left_pixel() {
+        let image = image_of(4, 2, &[RED, BLUE, BLUE, RED, BLUE, BLUE, RED, RED]);
+        let next = downsample(&image, MipFilter::Nearest);
+
+        assert_eq!(next.dimensions(), (2, 1));
+        assert_eq!(next.pixels().copied().collect::<Vec<_>>(), [RED, BLUE]);
+    }
+
+    #[test]
+    fn box_averages_each_square() {
+        let image = image_of(2, 2, &[RED, BLUE, BLUE, RED]);
+        let next = downsample(&image, MipFilter::Box);
+
+        assert_eq!(next.dimensions(), (1, 1));
+        assert_eq!(*next.get_pixel(0, 0), Rgba([128, 0, 128, 255]));
+    }
+
+    #[test]
+    fn transparent_colors_dont_bleed() {
+        let image = image_of(2, 2, &[RED, CLEAR, CLEAR, CLEAR]);
+        assert_eq!(
+            *downsample(&image, MipFilter::Box).get_pixel(0, 0),
+            Rgba([255, 0, 0, 64])
+        );
+
+        let clear = image_of(2, 2, &[CLEAR; 4]);
+        assert_eq!(*downsample(&clear, MipFilter::Box).get_pixel(0, 0), CLEAR);
+    }
+
+    #[test]
+    fn odd_sizes_leave_out_the_last_row_and_column() {
+        let image = image_of(3, 3, &[RED, RED, BLUE, RED, RED, BLUE, BLUE, BLUE, BLUE]);
+
+        for &filter in &[MipFilter::Nearest, MipFilter::Box] {
+            let next = downsample(&image, filter);
+            assert_eq!(next.dimensions(), (1, 1));
+            assert_eq!(*next.get_pixel(0, 0), RED, "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn thin_images_only_shrink_along_their_length() {
+        let image = image_of(4, 1, &[RED, RED, BLUE, BLUE]);
+        let chain = mip_chain(image, MipFilter::Box);
+
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[1].pixels().copied().collect::<Vec<_>>(), [RED, BLUE]);
+        assert_eq!(*chain[2].get_pixel(0, 0), Rgba([128, 0, 128, 255]));
+    }
+}
@@ -1,7 +1,10 @@
 mod errors;
 
-pub mod three;
+pub mod array;
+pub mod mipmap;
 pub mod two;
 
-pub use three::*;
+pub use array::*;
+pub use errors::TextureError;
+pub use mipmap::MipFilter;
 pub use two::*;
@@ -1,4 +1,5 @@
 use super::errors::TextureError;
+use super::mipmap::{self, MipFilter};
 use std::path::Path;
 
 pub struct Texture2d {
@@ -73,6 +74,8 @@ impl Texture2d {
         Self::from_image(device, img, label)
     }
 
+    /// Makes a texture out of the image, with mip levels made by taking the nearest pixel so
+    /// that sprites stay sharp when they're drawn small.
     pub fn from_image(
         device: &wgpu::Device,
         img: image::DynamicImage,
@@ -94,7 +97,7 @@ impl Texture2d {
 
             // multiple textures of the same size can be stored in one texture
             array_layer_count: 1,
-            mip_level_count: 1,
+            mip_level_count: mipmap::mip_level_count(dimensions),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -105,29 +108,12 @@ impl Texture2d {
             label,
         });
 
-        // COPY_SRC: copy from this buffer
-        let buffer = device
-            .create_buffer_with_data(bytemuck::cast_slice(&rgba), wgpu::BufferUsage::COPY_SRC);
-
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("texture command encoder"),
         });
 
-        encoder.copy_buffer_to_texture(
-            wgpu::BufferCopyView {
-                buffer: &buffer,
-                offset: 0,
-                bytes_per_row: 4 * dimensions.0,
-                rows_per_image: dimensions.1,
-            },
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                array_layer: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            size,
-        );
+        let chain = mipmap::mip_chain(rgba, MipFilter::Nearest);
+        mipmap::copy_mip_chain(device, &mut encoder, &texture, 0, &chain);
 
         let cmd_buffer = encoder.finish();
 
@@ -1,31 +1,104 @@
 #![allow(unused_variables)]
 
-use crate::blocks::Chunk;
+use crate::camera::Camera;
+use crate::characters::{Chicken, ChickenSprites};
+use crate::maths::Frustum;
+use crate::traits::{Logicable, Renderable};
+use crate::world::save::{SaveError, WorldSave};
+use crate::world::World;
+
+/// Directory the world is saved in, relative to the working directory.
+const SAVE_DIRECTORY: &str = "saves/default";
 
 pub(crate) struct Game {
-    // world: Option<World>,
-    tmp_chunk: Chunk,
+    world: World,
+    chicken: Chicken,
+
+    /// Layout of the bind groups that chunks keep their uniforms in.
+    chunk_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Where the camera was during the last logic update. Translucent chunks are sorted by their
+    /// distance from it.
+    camera_position: cgmath::Point3<f32>,
+
+    /// What the camera could see during the last logic update. Chunks outside of it aren't drawn.
+    frustum: Frustum,
 }
 
 impl Game {
     /// Decided to pass in bind groups and pipelines so that this file doesn't become too crowded.
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(
+        device: &wgpu::Device,
+        chunk_bind_group_layout: wgpu::BindGroupLayout,
+        chicken_sprites: ChickenSprites,
+    ) -> Self {
         println!("creating new game");
-        let tmp_chunk = Chunk::generate(0, 0, 0, &device);
+        let world = Self::open_world();
+        let mut chicken = Self::load_chicken(&world);
+        chicken.set_sprites(chicken_sprites);
         Self {
-            // world: None,
-            tmp_chunk,
+            world,
+            chicken,
+            chunk_bind_group_layout,
+            camera_position: cgmath::Point3::new(0.0, 0.0, 0.0),
+            frustum: Frustum::from_view_projection(Camera::default().get_view_projection_matrix()),
+        }
+    }
+
+    /// Loads the chicken from the world's save, or makes a new one if it wasn't saved.
+    fn load_chicken(world: &World) -> Chicken {
+        let chicken = match world.world_save().map(|save| save.load_player()) {
+            Some(Ok(chicken)) => chicken,
+            Some(Err(e)) => {
+                eprintln!("couldn't load the chicken: {}", e);
+                None
+            }
+            None => None,
+        };
+
+        chicken.unwrap_or_else(Chicken::new)
+    }
+
+    /// Opens the saved world, or makes a new one if there isn't one. If the save can't be used,
+    /// the game still runs with a world that isn't saved.
+    fn open_world() -> World {
+        let world = if WorldSave::exists(SAVE_DIRECTORY) {
+            println!("opening world saved in `{}`", SAVE_DIRECTORY);
+            World::open(SAVE_DIRECTORY)
+        } else {
+            World::create(SAVE_DIRECTORY, rand::random())
+        };
+
+        world.unwrap_or_else(|e| {
+            eprintln!("{}; this world won't be saved", e);
+            World::new(rand::random())
+        })
+    }
+
+    /// Saves the world and the chicken.
+    pub fn save(&mut self) -> Result<(), SaveError> {
+        self.world.save()?;
+        if let Some(save) = self.world.world_save() {
+            save.store_player(&self.chicken)?;
         }
+
+        Ok(())
     }
 
     fn start_render_pass<'a>(
         phase: RenderPhase,
         payload: &'a mut crate::RenderPayload,
     ) -> wgpu::RenderPass<'a> {
+        // only the first phase clears the frame; the others draw on top of it
+        let load_op = match phase {
+            RenderPhase::World => wgpu::LoadOp::Clear,
+            _ => wgpu::LoadOp::Load,
+        };
+
         let color_attachments = &[wgpu::RenderPassColorAttachmentDescriptor {
             attachment: &payload.frame,
             resolve_target: None,
-            load_op: wgpu::LoadOp::Clear,
+            load_op,
             store_op: wgpu::StoreOp::Store,
             clear_color: wgpu::Color {
                 r: 0.1,
@@ -39,10 +112,10 @@ impl Game {
         let depth_stencil_attachment = match phase {
             _ => Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
                 attachment: payload.depth_texture,
-                depth_load_op: wgpu::LoadOp::Clear,
+                depth_load_op: load_op,
                 depth_store_op: wgpu::StoreOp::Store,
                 clear_depth: 1.0,
-                stencil_load_op: wgpu::LoadOp::Clear,
+                stencil_load_op: load_op,
                 stencil_store_op: wgpu::StoreOp::Store,
                 clear_stencil: 0,
             }),
@@ -55,27 +128,70 @@ impl Game {
                 depth_stencil_attachment,
             });
 
-        #[allow(clippy::single_match)]
         match phase {
             RenderPhase::World => {
                 pass.set_pipeline(payload.block_render_pipeline);
                 pass.set_bind_group(0, payload.block_texture_bind_group, &[]);
+                pass.set_index_buffer(
+                    payload.quad_index_buffer,
+                    0,
+                    crate::blocks::render::quad_index_buffer_size(),
+                );
+            }
+            RenderPhase::Characters => {
+                pass.set_pipeline(payload.sprite_render_pipeline);
+            }
+            RenderPhase::Translucent => {
+                pass.set_pipeline(payload.translucent_block_render_pipeline);
+                pass.set_bind_group(0, payload.block_texture_bind_group, &[]);
+                pass.set_index_buffer(
+                    payload.quad_index_buffer,
+                    0,
+                    crate::blocks::render::quad_index_buffer_size(),
+                );
             }
-            _ => {}
+            RenderPhase::Interface => {}
         }
         pass.set_bind_group(1, payload.uniform_bind_group, &[]);
 
         pass
     }
 
-    pub fn logic(&mut self, device: &wgpu::Device, queue: &mut wgpu::Queue) {
-        self.tmp_chunk.logic(device);
+    pub fn logic(&mut self, device: &wgpu::Device, queue: &mut wgpu::Queue, camera: &Camera) {
+        self.camera_position = camera.position();
+        self.frustum = Frustum::from_view_projection(camera.get_view_projection_matrix());
+        self.world.stream_around(self.camera_position);
+        self.world.logic(device, &self.chunk_bind_group_layout);
+    }
+
+    /// Moves the characters along and gets their sprites ready to be drawn facing the camera.
+    /// Called after `logic`, which moves the camera.
+    pub fn animate(&mut self, delta_sec: f32, device: &wgpu::Device, queue: &mut wgpu::Queue) {
+        self.chicken.logic(delta_sec);
+        self.chicken
+            .update_sprite(device, queue, self.camera_position);
     }
 
     pub fn render(&self, payload: &mut crate::RenderPayload) {
-        let mut world_render_pass = Self::start_render_pass(RenderPhase::World, payload);
+        {
+            let mut world_render_pass = Self::start_render_pass(RenderPhase::World, payload);
+            self.world.render(&mut world_render_pass, &self.frustum);
+        }
 
-        self.tmp_chunk.render(&mut world_render_pass);
+        {
+            let mut character_render_pass =
+                Self::start_render_pass(RenderPhase::Characters, payload);
+            Renderable::render(&self.chicken, &mut character_render_pass);
+        }
+
+        // translucent blocks go last so that a chicken behind water or glass shows through it
+        let mut translucent_render_pass =
+            Self::start_render_pass(RenderPhase::Translucent, payload);
+        self.world.render_translucent(
+            &mut translucent_render_pass,
+            self.camera_position,
+            &self.frustum,
+        );
     }
 }
 
@@ -87,6 +203,9 @@ enum RenderPhase {
     /// Draw characters.
     Characters,
 
+    /// Draw the blocks you can see through, like water and glass, over everything else.
+    Translucent,
+
     /// Draw the user interface: health bars, backpack view, buttons, et cetera.
     Interface,
 }
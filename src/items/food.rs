@@ -1,4 +1,7 @@
-#[derive(Debug)]
+use crate::traits::Persist;
+use crate::world::save::{Reader, SaveError};
+
+#[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
 pub enum Food {
     Apple,
@@ -10,3 +13,23 @@ pub enum Food {
     Raspberry,
     Nuts,
 }
+
+impl Persist for Food {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => Food::Apple,
+            1 => Food::Blueberry,
+            2 => Food::Carrot,
+            3 => Food::Lemon,
+            4 => Food::Orange,
+            5 => Food::Pineapple,
+            6 => Food::Raspberry,
+            7 => Food::Nuts,
+            id => return Err(SaveError::Corrupt(format!("unknown food id {}", id))),
+        })
+    }
+}
@@ -1,6 +1,9 @@
+use crate::traits::Persist;
+use crate::world::save::{Reader, SaveError};
+
 /// Materials apply usually only to tools or weapons. Material describes what something is made out
 /// of.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
 pub enum Material {
     Wood,
@@ -11,3 +14,22 @@ pub enum Material {
     Gold,
     Diamond,
 }
+
+impl Persist for Material {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => Material::Wood,
+            1 => Material::Stone,
+            2 => Material::Bronze,
+            3 => Material::Brass,
+            4 => Material::Steel,
+            5 => Material::Gold,
+            6 => Material::Diamond,
+            id => return Err(SaveError::Corrupt(format!("unknown material id {}", id))),
+        })
+    }
+}
@@ -3,9 +3,13 @@ mod material;
 mod resource;
 
 use crate::blocks::Block;
+#[cfg(test)]
+use crate::blocks::BlockType;
+use crate::traits::Persist;
+use crate::world::save::{Reader, SaveError};
 use food::Food;
 use material::Material;
-use resource::Resource;
+pub use resource::Resource;
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -18,6 +22,46 @@ pub enum Item {
     Other,
 }
 
+impl Persist for Item {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Item::Resource(resource) => {
+                bytes.push(0);
+                resource.write_to(bytes);
+            }
+            Item::Tool(tool) => {
+                bytes.push(1);
+                tool.write_to(bytes);
+            }
+            Item::Weapon(weapon) => {
+                bytes.push(2);
+                weapon.write_to(bytes);
+            }
+            Item::Block(block) => {
+                bytes.push(3);
+                block.write_to(bytes);
+            }
+            Item::Food(food) => {
+                bytes.push(4);
+                food.write_to(bytes);
+            }
+            Item::Other => bytes.push(5),
+        }
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => Item::Resource(Resource::read_from(reader)?),
+            1 => Item::Tool(Tool::read_from(reader)?),
+            2 => Item::Weapon(Weapon::read_from(reader)?),
+            3 => Item::Block(Block::read_from(reader)?),
+            4 => Item::Food(Food::read_from(reader)?),
+            5 => Item::Other,
+            id => return Err(SaveError::Corrupt(format!("unknown item kind {}", id))),
+        })
+    }
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Tool {
@@ -27,7 +71,25 @@ pub struct Tool {
     lifespan: u32,
 }
 
-#[derive(Debug)]
+impl Persist for Tool {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        self.tool_type.write_to(bytes);
+        self.material.write_to(bytes);
+        bytes.extend_from_slice(&self.health.to_le_bytes());
+        bytes.extend_from_slice(&self.lifespan.to_le_bytes());
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Self {
+            tool_type: ToolType::read_from(reader)?,
+            material: Material::read_from(reader)?,
+            health: reader.read_u32()?,
+            lifespan: reader.read_u32()?,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
 pub enum ToolType {
     Shovel,
@@ -36,6 +98,22 @@ pub enum ToolType {
     Hoe,
 }
 
+impl Persist for ToolType {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => ToolType::Shovel,
+            1 => ToolType::Axe,
+            2 => ToolType::Pick,
+            3 => ToolType::Hoe,
+            id => return Err(SaveError::Corrupt(format!("unknown tool type id {}", id))),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Weapon {
     tool_type: WeaponType,
@@ -44,7 +122,25 @@ pub struct Weapon {
     lifespan: f32,
 }
 
-#[derive(Debug)]
+impl Persist for Weapon {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        self.tool_type.write_to(bytes);
+        self.material.write_to(bytes);
+        bytes.extend_from_slice(&self.health.to_le_bytes());
+        bytes.extend_from_slice(&self.lifespan.to_le_bytes());
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Self {
+            tool_type: WeaponType::read_from(reader)?,
+            material: Material::read_from(reader)?,
+            health: reader.read_f32()?,
+            lifespan: reader.read_f32()?,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
 pub enum WeaponType {
     Sword,
@@ -52,9 +148,99 @@ pub enum WeaponType {
     Arrow,
 }
 
+impl Persist for WeaponType {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => WeaponType::Sword,
+            1 => WeaponType::Bow,
+            2 => WeaponType::Arrow,
+            id => return Err(SaveError::Corrupt(format!("unknown weapon type id {}", id))),
+        })
+    }
+}
+
 /// A tuple; the first element is the Item and the second is the size of the stack.
 pub type ItemStack = (Item, u8);
 
 /// Backpack is really just a fun alternative name for "item vector" and "inventory" :) It is a
 /// vector of ItemStacks.
 pub type Backpack = Vec<ItemStack>;
+
+/// Returns a backpack holding a stack of every kind of item, for tests of things that save them.
+#[cfg(test)]
+pub fn backpack_of_every_kind() -> Backpack {
+    vec![
+        (Item::Resource(Resource::GoldNugget), 7),
+        (
+            Item::Tool(Tool {
+                tool_type: ToolType::Pick,
+                material: Material::Steel,
+                health: 40,
+                lifespan: 250,
+            }),
+            1,
+        ),
+        (
+            Item::Weapon(Weapon {
+                tool_type: WeaponType::Bow,
+                material: Material::Wood,
+                health: 12.5,
+                lifespan: 60.0,
+            }),
+            1,
+        ),
+        (
+            Item::Block(Block::new(
+                BlockType::Stone,
+                0.75,
+                Some(cgmath::Vector3::new(0.25, 0.0, -0.5)),
+            )),
+            64,
+        ),
+        (Item::Food(Food::Raspberry), 12),
+        (Item::Other, 1),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_item(bytes: &[u8]) -> Result<Item, SaveError> {
+        Item::read_from(&mut Reader::new(bytes))
+    }
+
+    #[test]
+    fn every_kind_of_item_round_trips() {
+        for (item, _) in backpack_of_every_kind() {
+            let mut bytes = Vec::new();
+            item.write_to(&mut bytes);
+
+            let read = read_item(&bytes).unwrap();
+            assert_eq!(format!("{:?}", read), format!("{:?}", item));
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_corrupt() {
+        let unknown = [
+            vec![6],                               // item kind
+            vec![0, 10],                           // resource
+            vec![1, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0], // tool type
+            vec![1, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0], // tool material
+            vec![2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0], // weapon type
+            vec![4, 8],                            // food
+        ];
+
+        for bytes in unknown.iter() {
+            match read_item(bytes) {
+                Err(SaveError::Corrupt(_)) => {}
+                other => panic!("{:?} read as {:?}", bytes, other),
+            }
+        }
+    }
+}
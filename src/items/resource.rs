@@ -1,6 +1,9 @@
+use crate::traits::Persist;
+use crate::world::save::{Reader, SaveError};
+
 /// A Resource is something that can be crafted into something else. A Resource can't be placed
 /// down like a Block can be, nor is it edible like Food.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
 pub enum Resource {
     WoodPlanks,
@@ -14,3 +17,44 @@ pub enum Resource {
     Diamond,
     Coal,
 }
+
+impl Resource {
+    /// Returns the resource with the name it goes by in block definitions, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "wood_planks" => Resource::WoodPlanks,
+            "rocks" => Resource::Rocks,
+            "sticks" => Resource::Sticks,
+            "aluminium" => Resource::Aluminium,
+            "iron_ingot" => Resource::IronIngot,
+            "iron_nugget" => Resource::IronNugget,
+            "gold_ingot" => Resource::GoldIngot,
+            "gold_nugget" => Resource::GoldNugget,
+            "diamond" => Resource::Diamond,
+            "coal" => Resource::Coal,
+            _ => return None,
+        })
+    }
+}
+
+impl Persist for Resource {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(*self as u8);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match reader.read_u8()? {
+            0 => Resource::WoodPlanks,
+            1 => Resource::Rocks,
+            2 => Resource::Sticks,
+            3 => Resource::Aluminium,
+            4 => Resource::IronIngot,
+            5 => Resource::IronNugget,
+            6 => Resource::GoldIngot,
+            7 => Resource::GoldNugget,
+            8 => Resource::Diamond,
+            9 => Resource::Coal,
+            id => return Err(SaveError::Corrupt(format!("unknown resource id {}", id))),
+        })
+    }
+}
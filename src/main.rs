@@ -8,6 +8,7 @@
 mod blocks;
 mod camera;
 mod characters;
+mod defs;
 mod engine;
 mod game;
 mod items;
@@ -27,6 +28,59 @@ use winit::{
 };
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // `--resource-pack <directory>` draws blocks with the textures in a resource pack instead of
+    // the built-in ones. A pack that can't be opened falls back to the built-in textures.
+    let resource_pack = match args.iter().position(|arg| arg == "--resource-pack") {
+        Some(i) => {
+            let pack = args
+                .get(i + 1)
+                .ok_or_else(|| String::from("`--resource-pack` needs a directory"))
+                .and_then(|directory| {
+                    textures::ResourcePack::open(directory).map_err(|e| e.to_string())
+                });
+            match pack {
+                Ok(pack) => Some(pack),
+                Err(e) => {
+                    eprintln!(
+                        "couldn't open resource pack: {}; using the built-in textures",
+                        e
+                    );
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // blocks can only use textures that there are images for
+    let find_texture = |name: &str| textures::BlockTextures::resolve(name, resource_pack.as_ref());
+    if let Err(e) = blocks::registry::init(&find_texture) {
+        eprintln!("couldn't load block definitions: {}", e);
+        std::process::exit(1);
+    }
+
+    // `--ore-stats [seed]` prints how many of each ore generate in a region around the origin and
+    // exits without opening a window. Handy for tuning ore generation.
+    if args.get(1).map(String::as_str) == Some("--ore-stats") {
+        let seed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(0);
+        let generator = world::terrain::TerrainGenerator::new(seed);
+        let stats = world::ores::OreStatistics::gather(&generator, (-4, -8, -4), (3, 1, 3));
+        println!("ore statistics for seed {}:\n{}", seed, stats);
+        return;
+    }
+
+    // `--chunk-memory [seed]` compares how much memory generated chunks take up with their
+    // palette storage against a plain array of blocks, then exits.
+    if args.get(1).map(String::as_str) == Some("--chunk-memory") {
+        let seed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(0);
+        let generator = world::terrain::TerrainGenerator::new(seed);
+        let stats = blocks::MemoryStatistics::gather(&generator, (-2, -3, -2), (1, 2, 1));
+        println!("chunk memory for seed {}:\n{}", seed, stats);
+        return;
+    }
+
     println!("PRINTING ON MAIN");
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
@@ -49,8 +103,8 @@ fn main() {
                         visibility: wgpu::ShaderStage::FRAGMENT,
                         ty: wgpu::BindingType::SampledTexture {
                             multisampled: false,
-                            dimension: wgpu::TextureViewDimension::D3,
-                            component_type: wgpu::TextureComponentType::Uint,
+                            dimension: wgpu::TextureViewDimension::D2Array,
+                            component_type: wgpu::TextureComponentType::Float,
                         },
                     },
                     wgpu::BindGroupLayoutEntry {
@@ -64,17 +118,18 @@ fn main() {
 
     let texture_dimensions = (16, 16);
 
-    let default_textures = {
+    let block_textures = {
         use textures::BlockTextures;
 
-        let (textures, cmds) = match BlockTextures::default_textures(
+        let (textures, cmds) = match BlockTextures::load(
             engine.get_device(),
+            resource_pack.as_ref(),
             texture_dimensions,
             &block_texture_bind_group_layout,
         ) {
             Ok(tc) => tc,
             Err(e) => {
-                eprintln!("couldn't make default textures: {}", e);
+                eprintln!("couldn't make block textures: {}", e);
                 std::process::exit(1);
             }
         };
@@ -125,11 +180,48 @@ fn main() {
             label: Some("uniform bind group"),
         });
 
+    // each chunk keeps its position in a bind group of its own
+    let chunk_bind_group_layout = blocks::render::make_chunk_bind_group_layout(engine.get_device());
+
+    // every chunk mesh is drawn with the same quad indices
+    let quad_index_buffer = blocks::render::make_quad_index_buffer(engine.get_device());
+
     // chunk render pipeline
     let block_render_pipeline = match blocks::render::make_chunk_render_pipeline(
         &mut engine,
         &block_texture_bind_group_layout,
         &uniform_bind_group_layout,
+        &chunk_bind_group_layout,
+    ) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // translucent chunk render pipeline, drawn after the opaque one
+    let translucent_block_render_pipeline =
+        match blocks::render::make_translucent_chunk_render_pipeline(
+            &mut engine,
+            &block_texture_bind_group_layout,
+            &uniform_bind_group_layout,
+            &chunk_bind_group_layout,
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+    // characters are drawn as sprites, each with a bind group of its own
+    let sprite_bind_group_layout = sprite::make_sprite_bind_group_layout(engine.get_device());
+
+    let sprite_render_pipeline = match sprite::make_sprite_render_pipeline(
+        &mut engine,
+        &sprite_bind_group_layout,
+        &uniform_bind_group_layout,
     ) {
         Ok(p) => p,
         Err(e) => {
@@ -138,10 +230,26 @@ fn main() {
         }
     };
 
+    let chicken_sprites =
+        match characters::ChickenSprites::load(engine.get_device(), &sprite_bind_group_layout) {
+            Ok((sprites, cmds)) => {
+                engine.get_queue().submit(&cmds);
+                sprites
+            }
+            Err(e) => {
+                eprintln!("couldn't load the chicken's sprites: {}", e);
+                std::process::exit(1);
+            }
+        };
+
     let camera = camera::Camera::default();
     let camera_controller = camera::CameraController::new(5.0, 1.0);
 
-    let game = game::Game::new(engine.get_device());
+    let game = game::Game::new(
+        engine.get_device(),
+        chunk_bind_group_layout,
+        chicken_sprites,
+    );
 
     let runner = MainRunner {
         state: GameState::Game(Box::new(game)),
@@ -151,9 +259,12 @@ fn main() {
         uniform_bind_group,
         // uniform_bind_group_layout,
         block_render_pipeline,
+        translucent_block_render_pipeline,
+        quad_index_buffer,
+        sprite_render_pipeline,
         camera,
         camera_controller,
-        block_textures: default_textures,
+        block_textures,
     };
 
     engine.set_runner(runner);
@@ -172,6 +283,21 @@ struct MainRunner {
 
     block_textures: textures::BlockTextures,
     block_render_pipeline: wgpu::RenderPipeline,
+    translucent_block_render_pipeline: wgpu::RenderPipeline,
+    quad_index_buffer: wgpu::Buffer,
+    sprite_render_pipeline: wgpu::RenderPipeline,
+}
+
+impl MainRunner {
+    fn save(&mut self) {
+        match &mut self.state {
+            GameState::Game(g) => {
+                if let Err(e) = g.save() {
+                    eprintln!("couldn't save the game: {}", e);
+                }
+            }
+        }
+    }
 }
 
 impl engine::Runner for MainRunner {
@@ -190,6 +316,11 @@ impl engine::Runner for MainRunner {
         } else {
             self.camera_controller.input(event);
         }
+
+        // the engine exits on its own when the window is closed
+        if *control_flow == ControlFlow::Exit || *event == WindowEvent::CloseRequested {
+            self.save();
+        }
     }
 
     fn device_event(&mut self, event: &DeviceEvent) {
@@ -201,11 +332,20 @@ impl engine::Runner for MainRunner {
     fn update(&mut self, delta_sec: f32, device: &wgpu::Device, queue: &mut wgpu::Queue) -> bool {
         self.camera_controller
             .update_camera(delta_sec, &mut self.camera);
-        self.uniforms
-            .update(device, &self.camera, &mut self.uniform_buffer, queue);
+        self.block_textures.update(delta_sec);
+        self.uniforms.update(
+            device,
+            &self.camera,
+            &self.block_textures,
+            &mut self.uniform_buffer,
+            queue,
+        );
 
         match &mut self.state {
-            GameState::Game(g) => g.logic(device, queue),
+            GameState::Game(g) => {
+                g.logic(device, queue, &self.camera);
+                g.animate(delta_sec, device, queue);
+            }
         }
 
         true
@@ -225,6 +365,9 @@ impl engine::Runner for MainRunner {
             frame,
             depth_texture,
             block_render_pipeline: &self.block_render_pipeline,
+            translucent_block_render_pipeline: &self.translucent_block_render_pipeline,
+            quad_index_buffer: &self.quad_index_buffer,
+            sprite_render_pipeline: &self.sprite_render_pipeline,
             uniform_bind_group: &self.uniform_bind_group,
             block_texture_bind_group: &self.block_textures.get_bind_group(),
         };
@@ -248,6 +391,9 @@ struct RenderPayload<'a> {
     frame: &'a wgpu::TextureView,
     depth_texture: &'a wgpu::TextureView,
     block_render_pipeline: &'a wgpu::RenderPipeline,
+    translucent_block_render_pipeline: &'a wgpu::RenderPipeline,
+    quad_index_buffer: &'a wgpu::Buffer,
+    sprite_render_pipeline: &'a wgpu::RenderPipeline,
     block_texture_bind_group: &'a wgpu::BindGroup,
     uniform_bind_group: &'a wgpu::BindGroup,
 }
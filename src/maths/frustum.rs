@@ -0,0 +1,176 @@
+use super::AABB;
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+/// A plane, stored as a unit normal and the plane's signed distance from the origin along it.
+/// Points on the side the normal faces have a positive distance to the plane.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Makes a plane out of the coefficients of `ax + by + cz + d = 0`.
+    fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.truncate();
+        let length = normal.magnitude();
+        Self {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    /// Returns how far `point` is in front of the plane. Points behind it give negative numbers.
+    pub fn signed_distance(&self, point: Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// The volume the camera can see, bounded by six planes that face into it.
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, in that order.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Pulls the frustum's planes out of a view-projection matrix. The matrix is expected to clip
+    /// depth to -w..w like the ones `cgmath::perspective` makes, which puts the near plane a
+    /// little closer than it ends up on the GPU. That only ever keeps extra chunks.
+    pub fn from_view_projection(matrix: &Matrix4<f32>) -> Self {
+        let (x, y, z, w) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+
+        Self {
+            planes: [
+                Plane::from_coefficients(w + x),
+                Plane::from_coefficients(w - x),
+                Plane::from_coefficients(w + y),
+                Plane::from_coefficients(w - y),
+                Plane::from_coefficients(w + z),
+                Plane::from_coefficients(w - z),
+            ],
+        }
+    }
+
+    /// Returns false if the box is entirely outside the frustum. Boxes near the frustum's corners
+    /// can be let through even though they're outside it, which is fine for culling.
+    pub fn intersects(&self, aabb: &AABB) -> bool {
+        self.planes.iter().all(|plane| {
+            // how far the box reaches towards the plane from its center
+            let reach = plane.normal.x.abs() * aabb.half_size.x
+                + plane.normal.y.abs() * aabb.half_size.y
+                + plane.normal.z.abs() * aabb.half_size.z;
+
+            plane.signed_distance(aabb.center_pos) >= -reach
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Point3, SquareMatrix};
+
+    fn camera_matrix(eye: (f32, f32, f32), target: (f32, f32, f32)) -> Matrix4<f32> {
+        let view = Matrix4::look_at(
+            Point3::new(eye.0, eye.1, eye.2),
+            Point3::new(target.0, target.1, target.2),
+            Vector3::unit_y(),
+        );
+        cgmath::perspective(Deg(90.0), 1.0, 1.0, 100.0) * view
+    }
+
+    fn cube(center: (f32, f32, f32), half_size: f32) -> AABB {
+        AABB {
+            center_pos: Vector3::new(center.0, center.1, center.2),
+            half_size: Vector3::new(half_size, half_size, half_size),
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn identity_matrix_gives_unit_cube() {
+        let frustum = Frustum::from_view_projection(&Matrix4::identity());
+        let normals = [
+            Vector3::unit_x(),
+            -Vector3::unit_x(),
+            Vector3::unit_y(),
+            -Vector3::unit_y(),
+            Vector3::unit_z(),
+            -Vector3::unit_z(),
+        ];
+
+        for (plane, normal) in frustum.planes.iter().zip(normals.iter()) {
+            assert_eq!(plane.normal, *normal);
+            assert_close(plane.distance, 1.0);
+        }
+    }
+
+    #[test]
+    fn perspective_planes_are_where_the_camera_clips() {
+        // looking down -z from the origin
+        let frustum = Frustum::from_view_projection(&camera_matrix((0., 0., 0.), (0., 0., -1.)));
+        let [left, right, bottom, top, near, far] = frustum.planes;
+
+        assert_close(near.signed_distance(Vector3::new(0., 0., -1.)), 0.0);
+        assert_close(far.signed_distance(Vector3::new(0., 0., -100.)), 0.0);
+        assert_close(near.signed_distance(Vector3::new(0., 0., -3.)), 2.0);
+
+        // a 90 degree field of view puts the sides at 45 degrees
+        for plane in &[left, right, bottom, top] {
+            assert_close(plane.distance, 0.0);
+        }
+        assert_close(left.signed_distance(Vector3::new(-10., 0., -10.)), 0.0);
+        assert_close(right.signed_distance(Vector3::new(10., 0., -10.)), 0.0);
+        assert_close(bottom.signed_distance(Vector3::new(0., -10., -10.)), 0.0);
+        assert_close(top.signed_distance(Vector3::new(0., 10., -10.)), 0.0);
+        assert!(left.signed_distance(Vector3::new(0., 0., -10.)) > 0.0);
+    }
+
+    #[test]
+    fn boxes_in_front_of_the_camera_are_kept() {
+        let frustum = Frustum::from_view_projection(&camera_matrix((0., 0., 0.), (0., 0., -1.)));
+
+        assert!(frustum.intersects(&cube((0., 0., -10.), 1.0)));
+        assert!(frustum.intersects(&cube((0., 0., -50.), 16.0)));
+        // the camera is inside this one
+        assert!(frustum.intersects(&cube((0., 0., 0.), 16.0)));
+    }
+
+    #[test]
+    fn boxes_outside_the_frustum_are_culled() {
+        let frustum = Frustum::from_view_projection(&camera_matrix((0., 0., 0.), (0., 0., -1.)));
+
+        // behind the camera
+        assert!(!frustum.intersects(&cube((0., 0., 10.), 1.0)));
+        // off to the side
+        assert!(!frustum.intersects(&cube((30., 0., -10.), 1.0)));
+        assert!(!frustum.intersects(&cube((0., -30., -10.), 1.0)));
+        // past the far plane
+        assert!(!frustum.intersects(&cube((0., 0., -200.), 1.0)));
+    }
+
+    #[test]
+    fn boxes_poking_into_the_frustum_are_kept() {
+        let frustum = Frustum::from_view_projection(&camera_matrix((0., 0., 0.), (0., 0., -1.)));
+
+        // centered outside the right plane but reaching over it
+        assert!(!frustum.intersects(&cube((16., 0., -10.), 2.0)));
+        assert!(frustum.intersects(&cube((16., 0., -10.), 4.0)));
+        // straddling the far plane
+        assert!(frustum.intersects(&cube((0., 0., -101.), 2.0)));
+    }
+
+    #[test]
+    fn frustum_follows_the_camera() {
+        let frustum =
+            Frustum::from_view_projection(&camera_matrix((100., 20., 0.), (101., 20., 0.)));
+
+        assert!(frustum.intersects(&cube((120., 20., 0.), 1.0)));
+        assert!(!frustum.intersects(&cube((80., 20., 0.), 1.0)));
+        assert!(!frustum.intersects(&cube((0., 0., -10.), 1.0)));
+    }
+}
@@ -1,5 +1,7 @@
 mod aabb;
+mod frustum;
 // mod vectors;
 
 pub use self::aabb::*;
+pub use self::frustum::*;
 // pub use self::vectors::*;
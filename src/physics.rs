@@ -1,4 +1,6 @@
 use crate::maths;
+use crate::traits::Persist;
+use crate::world::save::{Reader, SaveError};
 use cgmath::Vector3;
 
 /// The gravity acceleration constant (m/s/s)
@@ -51,7 +53,7 @@ impl PhysicalObject {
     }
 
     /// Returns the PhysicalObject's position
-    fn position(&self) -> Vector3<f32> {
+    pub fn position(&self) -> Vector3<f32> {
         self.hitbox.center_pos
     }
 
@@ -161,6 +163,34 @@ impl PhysicalObject {
     }
 }
 
+/// Saves the parts of a PhysicalObject that carry over between frames. Acceleration is reset every
+/// frame and the contact flags are worked out again by the next collision checks, so those start
+/// out cleared when loaded.
+impl Persist for PhysicalObject {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.frozen as u8);
+        self.velocity.write_to(bytes);
+        bytes.extend_from_slice(&self.mass.to_le_bytes());
+        self.hitbox.center_pos.write_to(bytes);
+        self.hitbox.half_size.write_to(bytes);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        let frozen = reader.read_bool()?;
+        let velocity = Vector3::read_from(reader)?;
+        let mass = reader.read_f32()?;
+        let hitbox = maths::AABB {
+            center_pos: Vector3::read_from(reader)?,
+            half_size: Vector3::read_from(reader)?,
+        };
+
+        let mut p = Self::new(mass, hitbox);
+        p.frozen = frozen;
+        p.velocity = velocity;
+        Ok(p)
+    }
+}
+
 const FLOAT_ERROR: f32 = 0.0001;
 
 /// Returns a breach.
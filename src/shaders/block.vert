@@ -1,19 +1,66 @@
 #version 450
 
-layout(location=0) in vec3 a_position;
-layout(location=1) in vec2 a_tex_coords;
-layout(location=2) in uint a_layer;
+// see `ChunkMeshVertex` for how vertices are packed
+layout(location=0) in uvec2 a_packed;
 
 layout(set=1, binding=0) 
 uniform Uniforms {
     mat4 u_view_proj;
+
+    // the layer each block texture is showing this frame, packed four to an element. see
+    // `Uniforms::block_texture_layers`
+    uvec4 u_block_texture_layers[64];
+};
+
+layout(set=2, binding=0)
+uniform ChunkUniforms {
+    vec3 u_chunk_position;
+    float u_block_width;
 };
 
 layout(location=0) out vec2 v_tex_coords;
-layout(location=1) out uint v_layer;
+layout(location=1) flat out uint v_layer;
+layout(location=2) out float v_ambient_occlusion;
+layout(location=3) out vec3 v_light_color;
+
+// how light levels from `world::light` turn into colors. each level is a bit dimmer than the
+// one above it, and block light is a little warmer than sky light.
+const float MAX_LIGHT = 15.0;
+const float MIN_BRIGHTNESS = 0.05;
+const float LEVEL_FALLOFF = 0.8;
+const vec3 BLOCK_LIGHT_COLOR = vec3(1.0, 0.9, 0.7);
+
+float brightness(uint level) {
+    return MIN_BRIGHTNESS + (1.0 - MIN_BRIGHTNESS) * pow(LEVEL_FALLOFF, MAX_LIGHT - float(level));
+}
+
+// texture coordinates for a position on a face, in blocks, so textures repeat once per block.
+// faces are ordered like `Direction`: north, south, east, west, up, down.
+vec2 face_tex_coords(uvec3 position, uint face) {
+    vec3 p = vec3(position);
+    switch (face) {
+        case 0: return vec2(p.x, -p.y);
+        case 1: return vec2(-p.x, -p.y);
+        case 2: return vec2(p.z, -p.y);
+        case 3: return vec2(-p.z, -p.y);
+        case 4: return vec2(-p.x, -p.z);
+        default: return vec2(p.x, -p.z);
+    }
+}
 
 void main() {
-    v_tex_coords = a_tex_coords;
-    v_layer = a_layer;
-    gl_Position = u_view_proj * vec4(a_position, 1.0);
+    uvec3 position = uvec3(a_packed.x, a_packed.x >> 6, a_packed.x >> 12) & 0x3fu;
+    uint face = (a_packed.x >> 18) & 0x7u;
+    uint ambient_occlusion = (a_packed.x >> 21) & 0x3u;
+    uint sky_light = (a_packed.y >> 12) & 0xfu;
+    uint block_light = (a_packed.y >> 8) & 0xfu;
+
+    v_tex_coords = face_tex_coords(position, face);
+    uint texture = a_packed.y & 0xffu;
+    v_layer = u_block_texture_layers[texture / 4u][texture % 4u];
+    v_ambient_occlusion = float(ambient_occlusion) / 3.0;
+    v_light_color = max(vec3(brightness(sky_light)), brightness(block_light) * BLOCK_LIGHT_COLOR);
+
+    vec3 world_position = u_chunk_position + vec3(position) * u_block_width;
+    gl_Position = u_view_proj * vec4(world_position, 1.0);
 }
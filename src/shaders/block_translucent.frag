@@ -0,0 +1,27 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) flat in uint v_layer;
+layout(location=2) in float v_ambient_occlusion;
+layout(location=3) in vec3 v_light_color;
+
+layout(location=0) out vec4 f_color;
+
+// uniform uses `set` and `binding`
+// set=0 corresponds to the first parameter in set_bind_group.
+// binding=0 relates the binding specified when creating the BindGroupLayout
+// and BindGroup.
+layout(set=0, binding=0) uniform texture2DArray t_diffuse;
+layout(set=0, binding=1) uniform sampler s_diffuse;
+
+// how dark a fully occluded corner gets, compared to one out in the open
+const float AMBIENT_OCCLUSION_STRENGTH = 0.6;
+
+vec4 shade(vec4 color) {
+    float light = 1.0 - AMBIENT_OCCLUSION_STRENGTH * (1.0 - v_ambient_occlusion);
+    return vec4(color.rgb * v_light_color * light, color.a);
+}
+
+void main() {
+    f_color = shade(texture(sampler2DArray(t_diffuse, s_diffuse), vec3(v_tex_coords, float(v_layer))));
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_sprite;
+layout(set=0, binding=1) uniform sampler s_sprite;
+
+// pixels more transparent than this aren't drawn, so sprites don't need to be sorted
+const float ALPHA_CUTOFF = 0.5;
+
+void main() {
+    vec4 color = texture(sampler2D(t_sprite, s_sprite), v_tex_coords);
+    if (color.a < ALPHA_CUTOFF) {
+        discard;
+    }
+    f_color = color;
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(location=0) in vec2 a_position;
+layout(location=1) in vec2 a_tex_coords;
+
+layout(set=0, binding=2)
+uniform SpriteUniforms {
+    mat4 u_matrix;
+
+    // the left edge of the frame that's showing and the width of a frame, in texture coordinates
+    vec2 u_frame;
+};
+
+layout(set=1, binding=0)
+uniform Uniforms {
+    mat4 u_view_proj;
+};
+
+layout(location=0) out vec2 v_tex_coords;
+
+void main() {
+    v_tex_coords = vec2(u_frame.x + a_tex_coords.x * u_frame.y, a_tex_coords.y);
+    gl_Position = u_view_proj * u_matrix * vec4(a_position, 0.0, 1.0);
+}
@@ -1,101 +1,513 @@
-// use std::error::Error;
-// use cgmath::Matrix4;
-
-// /// An image that can be animated.
-// pub struct Sprite {
-//     texture:  u32,
-//     frames:   i32,
-//     uv_coords: (f32, f32),
-
-//     current_frame:    f32,
-//     seconds_per_frame: f32,
-
-//     size_matrix:     Matrix4,
-//     position_matrix: Matrix4,
-//     matrix:         Matrix4,
-
-//     pixel_width: i32,
-//     pixel_height: i32,
-// }
-
-// /// Creates a new sprite and returns it
-// impl Sprite {
-//     pub fn new(sprite_path: String, frames: i32, seconds_per_frame: f32) -> Result<Self, Box<dyn Error>> {
-//         let s: Self = Default::default();
-
-//         if frames <= 0 {
-//             frames = 1;
-//             if seconds_per_frame <= 0 {
-//                 seconds_per_frame = 1;
-//             }
-//         } else if seconds_per_frame <= 0 {
-//             return Err("seconds_per_frame cannot be less than or equal to 0 if frames is greater than 0");
-//         }
-
-//         // open the sprite file
-//         let sprite_file = fs::open(sprite_path)?;
-
-//         // assign the sprite texture
-//         s.texture = textures::new(sprite_file)?;
-
-//         // initialize the rest of the fields
-//         s.frames = frames;
-//         s.seconds_per_frame = seconds_per_frame;
-
-//         Ok(s)
-//     }
-
-//     /// Animates the Sprite.
-//     fn animate(&self, delta: f32) {
-//         // if one frame or less, animation doesn't matter
-//         if self.frames <= 1 {
-//             return
-//         }
-//         self.current_frame += delta / self.seconds_per_frame;
-//         for self.current_frame >= self.frames {
-//             self.current_frame -= self.frames;
-//         }
-//     }
-
-//     /// Sets the size of the sprite.
-//     fn set_size(&mut self, width: f32, height: f32) {
-//         self.size_matrix = Matrix4::identity().scale(&[width, height, 1.]);
-//         self.update_matrix();
-//     }
-
-//     fn get_pixel_width(&self) -> i32 {
-//         self.pixel_width
-//     }
-
-//     fn get_pixel_height(&self) -> i32 {
-//         self.pixel_height
-//     }
-
-//     /// Sets the position of the sprite.
-//     fn set_position(&mut self, x: f32, y: f32, z: f32) {
-//         self.position_matrix = Matrix4::identity().translate(&[x, y, z]);
-//         self.update_matrix();
-//     }
-
-//     fn update_matrix(&mut self) {
-//         self.matrix = self.position_matrix.mul4(self.size_matrix);
-//     }
-
-//     /// Renders the sprite onto the screen.
-//     fn render(&self, c: &render::Camera, plane_vao: i32) {
-
-//     }
-
-// }
-
-// const plane_vertices: [f32] = [
-//     // first triangle
-//     -0.5, 0.5, 0, 0, 0,
-//     -0.5, -0.5, 0, 0, 1,
-//     0.5, -0.5, 0, 1, 1,
-
-//     // second triangle
-//     -0.5, 0.5, 0, 0, 0,
-//     0.5, -0.5, 0, 1, 1,
-//     0.5, 0.5, 0, 1, 0,
-// ];
+use crate::engine::{self, Texture2d};
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+use std::error::Error;
+
+/// Which frame of a horizontal strip of frames is showing, and how long until the next one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpriteAnimation {
+    frames: u32,
+    seconds_per_frame: f32,
+
+    /// How far through the strip the animation is, in frames. The whole part is the frame that's
+    /// showing.
+    current_frame: f32,
+}
+
+impl SpriteAnimation {
+    /// Makes an animation that starts on the first frame. A strip of 0 frames is treated as a
+    /// single frame, and single frames don't need a frame time since they never change.
+    pub fn new(frames: u32, seconds_per_frame: f32) -> Result<Self, Box<dyn Error>> {
+        let frames = frames.max(1);
+        if frames > 1 && (seconds_per_frame.is_nan() || seconds_per_frame <= 0.0) {
+            return Err(format!(
+                "a sprite with {} frames needs more than 0 seconds per frame, not {}",
+                frames, seconds_per_frame
+            )
+            .into());
+        }
+
+        Ok(Self {
+            frames,
+            seconds_per_frame,
+            current_frame: 0.0,
+        })
+    }
+
+    /// Moves the animation along by the time since it was last moved, going back to the first
+    /// frame after the last.
+    pub fn animate(&mut self, delta: f32) {
+        // if one frame or less, animation doesn't matter
+        if self.frames <= 1 {
+            return;
+        }
+
+        self.current_frame += delta / self.seconds_per_frame;
+        self.current_frame = self.current_frame.rem_euclid(self.frames as f32);
+    }
+
+    /// Goes back to the first frame.
+    pub fn reset(&mut self) {
+        self.current_frame = 0.0;
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Returns the index of the frame that's showing.
+    pub fn frame(&self) -> u32 {
+        // the remainder can round up to exactly the frame count
+        (self.current_frame as u32).min(self.frames - 1)
+    }
+
+    /// Returns the left edge of the frame that's showing and the width of a frame, in texture
+    /// coordinates.
+    pub fn frame_tex_coords(&self) -> (f32, f32) {
+        let width = 1.0 / self.frames as f32;
+        (self.frame() as f32 * width, width)
+    }
+}
+
+/// An image that can be animated. Its texture is a strip of frames from left to right, all the
+/// same width, and it's drawn on a quad that's a unit square until it's given a size.
+pub struct Sprite {
+    texture: Texture2d,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+
+    animation: SpriteAnimation,
+
+    size_matrix: Matrix4<f32>,
+    rotation_matrix: Matrix4<f32>,
+    position_matrix: Matrix4<f32>,
+    matrix: Matrix4<f32>,
+
+    /// The size of a single frame.
+    pixel_width: u32,
+    pixel_height: u32,
+}
+
+impl Sprite {
+    /// Makes a sprite out of an image file.
+    pub fn load<P: AsRef<std::path::Path>>(
+        device: &wgpu::Device,
+        sprite_bind_group_layout: &wgpu::BindGroupLayout,
+        path: P,
+        frames: u32,
+        seconds_per_frame: f32,
+    ) -> Result<(Self, wgpu::CommandBuffer), Box<dyn Error>> {
+        let path = path.as_ref();
+        let img = image::open(path)
+            .map_err(|e| format!("couldn't load sprite `{}`: {}", path.display(), e))?;
+
+        Self::from_image(
+            device,
+            sprite_bind_group_layout,
+            img,
+            frames,
+            seconds_per_frame,
+            path.to_str(),
+        )
+    }
+
+    /// Makes a sprite out of an image with `frames` frames side by side, each shown for
+    /// `seconds_per_frame` seconds.
+    pub fn from_image(
+        device: &wgpu::Device,
+        sprite_bind_group_layout: &wgpu::BindGroupLayout,
+        img: image::DynamicImage,
+        frames: u32,
+        seconds_per_frame: f32,
+        label: Option<&str>,
+    ) -> Result<(Self, wgpu::CommandBuffer), Box<dyn Error>> {
+        use image::GenericImageView;
+
+        let animation = SpriteAnimation::new(frames, seconds_per_frame)?;
+        let (width, height) = img.dimensions();
+        if width % animation.frames() != 0 {
+            return Err(format!(
+                "sprite `{}` is {} pixels wide, which can't be split into {} frames",
+                label.unwrap_or("(no label)"),
+                width,
+                animation.frames()
+            )
+            .into());
+        }
+
+        let (texture, command_buffer) = Texture2d::from_image(device, img, label)?;
+
+        let matrix = Matrix4::identity();
+        let uniforms = SpriteUniforms::new(matrix, &animation);
+        let uniform_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[uniforms]),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: sprite_bind_group_layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                wgpu::Binding {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &uniform_buffer,
+                        range: 0..std::mem::size_of::<SpriteUniforms>() as wgpu::BufferAddress,
+                    },
+                },
+            ],
+            label: Some("sprite bind group"),
+        });
+
+        Ok((
+            Self {
+                texture,
+                uniform_buffer,
+                bind_group,
+                animation,
+                size_matrix: matrix,
+                rotation_matrix: matrix,
+                position_matrix: matrix,
+                matrix,
+                pixel_width: width / animation.frames(),
+                pixel_height: height,
+            },
+            command_buffer,
+        ))
+    }
+
+    /// Animates the Sprite.
+    pub fn animate(&mut self, delta: f32) {
+        self.animation.animate(delta);
+    }
+
+    pub fn animation(&self) -> &SpriteAnimation {
+        &self.animation
+    }
+
+    pub fn animation_mut(&mut self) -> &mut SpriteAnimation {
+        &mut self.animation
+    }
+
+    /// Sets the size of the sprite.
+    pub fn set_size(&mut self, width: f32, height: f32) {
+        self.size_matrix = Matrix4::from_nonuniform_scale(width, height, 1.0);
+        self.update_matrix();
+    }
+
+    /// Returns the width of a single frame, in pixels.
+    pub fn get_pixel_width(&self) -> u32 {
+        self.pixel_width
+    }
+
+    pub fn get_pixel_height(&self) -> u32 {
+        self.pixel_height
+    }
+
+    /// Sets how the sprite is turned around its center. Sprites face +z until they're turned.
+    pub fn set_rotation(&mut self, rotation: Matrix4<f32>) {
+        self.rotation_matrix = rotation;
+        self.update_matrix();
+    }
+
+    /// Sets the position of the sprite.
+    pub fn set_position(&mut self, x: f32, y: f32, z: f32) {
+        self.position_matrix = Matrix4::from_translation(Vector3::new(x, y, z));
+        self.update_matrix();
+    }
+
+    /// Returns the matrix that takes the quad to where the sprite is, at its size.
+    pub fn matrix(&self) -> &Matrix4<f32> {
+        &self.matrix
+    }
+
+    fn update_matrix(&mut self) {
+        self.matrix = self.position_matrix * self.rotation_matrix * self.size_matrix;
+    }
+
+    /// Copies the sprite's matrix and the frame that's showing to the GPU. Call this after
+    /// moving or animating the sprite, before it's drawn.
+    pub fn update_uniforms(&self, device: &wgpu::Device, queue: &mut wgpu::Queue) {
+        let uniforms = SpriteUniforms::new(self.matrix, &self.animation);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("sprite uniforms update command encoder"),
+        });
+
+        let staging_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[uniforms]),
+            wgpu::BufferUsage::COPY_SRC,
+        );
+
+        encoder.copy_buffer_to_buffer(
+            &staging_buffer,
+            0,
+            &self.uniform_buffer,
+            0,
+            std::mem::size_of::<SpriteUniforms>() as wgpu::BufferAddress,
+        );
+
+        queue.submit(&[encoder.finish()]);
+    }
+
+    /// Renders the sprite. This method assumes that the sprite pipeline and the uniform bind group
+    /// have already been bound to the render pass.
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, quad: &'a wgpu::Buffer) {
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, quad, 0, quad_vertex_buffer_size());
+        render_pass.draw(0..QUAD_VERTICES.len() as u32, 0..1);
+    }
+}
+
+/// Uniforms that are different for every sprite.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SpriteUniforms {
+    matrix: Matrix4<f32>,
+
+    /// The left edge of the frame that's showing and the width of a frame, in texture
+    /// coordinates.
+    frame: [f32; 2],
+    padding: [f32; 2],
+}
+
+impl SpriteUniforms {
+    fn new(matrix: Matrix4<f32>, animation: &SpriteAnimation) -> Self {
+        let (left, width) = animation.frame_tex_coords();
+        Self {
+            matrix,
+            frame: [left, width],
+            padding: [0.0; 2],
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for SpriteUniforms {}
+unsafe impl bytemuck::Zeroable for SpriteUniforms {}
+
+/// A corner of the quad every sprite is drawn on.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteVertex {
+    position: [f32; 2],
+
+    /// Where the corner is on a frame, from its top left.
+    tex_coords: [f32; 2],
+}
+
+impl SpriteVertex {
+    const SIZE: wgpu::BufferAddress = std::mem::size_of::<Self>() as wgpu::BufferAddress;
+
+    pub fn vertex_buffer_descriptors<'a>() -> &'a [wgpu::VertexBufferDescriptor<'a>] {
+        &[wgpu::VertexBufferDescriptor {
+            stride: Self::SIZE,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float2,
+                },
+            ],
+        }]
+    }
+}
+
+unsafe impl bytemuck::Pod for SpriteVertex {}
+unsafe impl bytemuck::Zeroable for SpriteVertex {}
+
+/// A unit square centered on the origin and facing +z, as two counterclockwise triangles.
+const QUAD_VERTICES: [SpriteVertex; 6] = [
+    // first triangle
+    SpriteVertex {
+        position: [-0.5, 0.5],
+        tex_coords: [0.0, 0.0],
+    },
+    SpriteVertex {
+        position: [-0.5, -0.5],
+        tex_coords: [0.0, 1.0],
+    },
+    SpriteVertex {
+        position: [0.5, -0.5],
+        tex_coords: [1.0, 1.0],
+    },
+    // second triangle
+    SpriteVertex {
+        position: [-0.5, 0.5],
+        tex_coords: [0.0, 0.0],
+    },
+    SpriteVertex {
+        position: [0.5, -0.5],
+        tex_coords: [1.0, 1.0],
+    },
+    SpriteVertex {
+        position: [0.5, 0.5],
+        tex_coords: [1.0, 0.0],
+    },
+];
+
+/// Makes the vertex buffer of the quad that every sprite is drawn on.
+pub fn make_quad_vertex_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer_with_data(
+        bytemuck::cast_slice(&QUAD_VERTICES),
+        wgpu::BufferUsage::VERTEX,
+    )
+}
+
+/// Returns the size, in bytes, of the buffer made by `make_quad_vertex_buffer`.
+pub fn quad_vertex_buffer_size() -> wgpu::BufferAddress {
+    QUAD_VERTICES.len() as wgpu::BufferAddress * SpriteVertex::SIZE
+}
+
+/// Makes the layout of the bind group that each sprite keeps its texture and uniforms in.
+pub fn make_sprite_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+        ],
+        label: Some("sprite bind group layout"),
+    })
+}
+
+/// Makes the pipeline that draws sprites. Like cutout blocks, transparent pixels are thrown away
+/// rather than blended, so sprites can be drawn in any order.
+pub fn make_sprite_render_pipeline(
+    engine: &mut engine::Engine,
+    sprite_bind_group_layout: &wgpu::BindGroupLayout,
+    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> Result<wgpu::RenderPipeline, Box<dyn Error>> {
+    let color_states = [wgpu::ColorStateDescriptor {
+        format: engine.get_swap_chain_descriptor().format,
+        color_blend: wgpu::BlendDescriptor::REPLACE,
+        alpha_blend: wgpu::BlendDescriptor::REPLACE,
+        write_mask: wgpu::ColorWrite::ALL,
+    }];
+
+    let (vs_module, fs_module) = engine.compile_shader_modules(
+        include_str!("shaders/sprite.vert"),
+        include_str!("shaders/sprite.frag"),
+    )?;
+
+    let render_pipeline_layout =
+        engine
+            .get_device()
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &[sprite_bind_group_layout, uniform_bind_group_layout],
+            });
+
+    let mut descriptor = crate::utils::make_render_pipeline_descriptor(
+        &render_pipeline_layout,
+        &vs_module,
+        &fs_module,
+        &color_states,
+        SpriteVertex::vertex_buffer_descriptors(),
+        true,
+    );
+
+    // sprites can be seen from behind, and flipping one with a negative size turns it around
+    if let Some(rasterization_state) = &mut descriptor.rasterization_state {
+        rasterization_state.cull_mode = wgpu::CullMode::None;
+    }
+
+    Ok(engine.get_device().create_render_pipeline(&descriptor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn frames_advance_with_time() {
+        let mut animation = SpriteAnimation::new(4, 0.2).unwrap();
+        assert_eq!(animation.frame(), 0);
+
+        animation.animate(0.1);
+        assert_eq!(animation.frame(), 0);
+        animation.animate(0.15);
+        assert_eq!(animation.frame(), 1);
+        animation.animate(0.4);
+        assert_eq!(animation.frame(), 3);
+    }
+
+    #[test]
+    fn frames_wrap_around() {
+        let mut animation = SpriteAnimation::new(4, 0.2).unwrap();
+        animation.animate(0.9);
+        assert_eq!(animation.frame(), 0);
+
+        // a long pause skips whole loops of the animation
+        animation.animate(0.2 * 4.0 * 100.0 + 0.5);
+        assert_eq!(animation.frame(), 3);
+
+        let mut many_steps = SpriteAnimation::new(3, 0.1).unwrap();
+        for _ in 0..1000 {
+            many_steps.animate(0.01);
+            assert!(many_steps.frame() < 3);
+        }
+    }
+
+    #[test]
+    fn still_sprites_never_change_frame() {
+        for &(frames, seconds_per_frame) in &[(0, 0.0), (1, 0.0), (1, 0.5), (0, -1.0)] {
+            let mut animation = SpriteAnimation::new(frames, seconds_per_frame).unwrap();
+            animation.animate(10.0);
+
+            assert_eq!(animation.frames(), 1);
+            assert_eq!(animation.frame(), 0);
+            assert_eq!(animation.frame_tex_coords(), (0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn animated_sprites_need_time_between_frames() {
+        assert!(SpriteAnimation::new(4, 0.0).is_err());
+        assert!(SpriteAnimation::new(2, -0.1).is_err());
+        assert!(SpriteAnimation::new(2, f32::NAN).is_err());
+    }
+
+    #[test]
+    fn frame_tex_coords_cover_the_frame() {
+        let mut animation = SpriteAnimation::new(4, 0.75).unwrap();
+        animation.animate(0.75 * 2.5);
+
+        let (left, width) = animation.frame_tex_coords();
+        assert_close(left, 0.5);
+        assert_close(width, 0.25);
+
+        animation.reset();
+        assert_eq!(animation.frame_tex_coords().0, 0.0);
+    }
+}
@@ -0,0 +1,137 @@
+use super::BlockTextureIndex;
+
+/// How long each frame of an animated texture shows for, in seconds, unless its resource pack
+/// says otherwise.
+pub const DEFAULT_FRAME_TIME: f32 = 0.25;
+
+/// The most layers the block texture array can have. It's also how many entries the block shader's
+/// table of current layers has.
+pub const MAX_BLOCK_TEXTURE_LAYERS: u32 = 256;
+
+/// A block texture whose image is a vertical strip of frames. The first frame goes in the
+/// texture's own layer, so meshes never need to know about the animation, and the other frames
+/// go in layers after all of the textures' own ones.
+#[derive(Debug)]
+pub struct TextureAnimation {
+    texture: BlockTextureIndex,
+
+    /// The layer of the second frame. The frames after it are in the layers after it.
+    later_frames_layer: u32,
+    frame_count: u32,
+
+    /// How long each frame shows for, in seconds.
+    frame_time: f32,
+
+    frame: u32,
+    time_in_frame: f32,
+}
+
+impl TextureAnimation {
+    pub fn new(
+        texture: BlockTextureIndex,
+        later_frames_layer: u32,
+        frame_count: u32,
+        frame_time: f32,
+    ) -> Self {
+        Self {
+            texture,
+            later_frames_layer,
+            frame_count,
+            frame_time,
+            frame: 0,
+            time_in_frame: 0.0,
+        }
+    }
+
+    pub fn texture(&self) -> BlockTextureIndex {
+        self.texture
+    }
+
+    /// Moves the animation along by the time since it was last moved.
+    pub fn advance(&mut self, delta_sec: f32) {
+        self.time_in_frame += delta_sec;
+
+        // a long pause can skip several frames at once
+        let skipped = (self.time_in_frame / self.frame_time) as u32;
+        self.frame = (self.frame + skipped % self.frame_count) % self.frame_count;
+        self.time_in_frame -= skipped as f32 * self.frame_time;
+    }
+
+    /// Returns the layer of the texture array with the frame that's showing.
+    pub fn current_layer(&self) -> u32 {
+        match self.frame {
+            0 => self.texture as u32,
+            frame => self.later_frames_layer + frame - 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Water with 4 frames, whose later frames start at layer 30.
+    fn water(frame_time: f32) -> TextureAnimation {
+        TextureAnimation::new(BlockTextureIndex::Water, 30, 4, frame_time)
+    }
+
+    #[test]
+    fn frames_advance_with_time() {
+        let mut animation = water(0.25);
+        assert_eq!(animation.current_layer(), BlockTextureIndex::Water as u32);
+
+        animation.advance(0.125);
+        assert_eq!(animation.current_layer(), BlockTextureIndex::Water as u32);
+        animation.advance(0.125);
+        assert_eq!(animation.current_layer(), 30);
+        animation.advance(0.5);
+        assert_eq!(animation.current_layer(), 32);
+    }
+
+    #[test]
+    fn frames_wrap_around() {
+        let mut animation = water(0.25);
+        animation.advance(0.75);
+        assert_eq!(animation.current_layer(), 32);
+        animation.advance(0.25);
+        assert_eq!(animation.current_layer(), BlockTextureIndex::Water as u32);
+
+        let mut many_steps = water(0.1);
+        for _ in 0..1000 {
+            many_steps.advance(0.01);
+            let layer = many_steps.current_layer();
+            assert!(layer == BlockTextureIndex::Water as u32 || (30..33).contains(&layer));
+        }
+    }
+
+    #[test]
+    fn long_pauses_skip_frames() {
+        let mut animation = water(0.25);
+        animation.advance(0.5);
+        assert_eq!(animation.current_layer(), 31);
+
+        // whole loops of the animation are skipped too
+        animation.advance(0.25 * 4.0 * 100.0 + 0.25);
+        assert_eq!(animation.current_layer(), 32);
+
+        // and the time left over counts towards the next frame
+        animation.advance(0.5 + 0.125);
+        assert_eq!(animation.current_layer(), 30);
+        animation.advance(0.125);
+        assert_eq!(animation.current_layer(), 31);
+    }
+
+    #[test]
+    fn only_later_frames_are_in_later_layers() {
+        let mut animation = TextureAnimation::new(BlockTextureIndex::Furnace, 100, 3, 1.0);
+        let mut layers = Vec::new();
+        for _ in 0..6 {
+            layers.push(animation.current_layer());
+            animation.advance(1.0);
+        }
+
+        let furnace = BlockTextureIndex::Furnace as u32;
+        assert_eq!(layers, [furnace, 100, 101, furnace, 100, 101]);
+        assert_eq!(animation.texture(), BlockTextureIndex::Furnace);
+    }
+}
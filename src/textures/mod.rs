@@ -1,79 +1,170 @@
 #![allow(dead_code)]
 
+pub mod animation;
+pub mod pack;
+
+pub use animation::*;
+pub use pack::ResourcePack;
+
 use crate::blocks::BlockType;
-use crate::engine;
+use crate::engine::{self, TextureError};
 use crate::world::Direction;
+use image::GenericImageView;
 use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 
-const BLOCK_TEXTURE_COUNT: u32 = 4;
+/// Number of layers in the block texture array.
+pub const BLOCK_TEXTURE_COUNT: u32 = BlockTextureIndex::ALL.len() as u32;
+
+/// A layer of the block texture array. Every face of a block is drawn with one of these, picked by
+/// `BlockTextureIndex::from_type_and_direction`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum BlockTextureIndex {
     Dirt,
+    /// The top of grass. Its sides have grass too, and its bottom is dirt.
     Grass,
     Stone,
     Sand,
+    Glass,
+    Leaves,
+    Cobblestone,
+    CoalOre,
+    IronOre,
+    CopperOre,
+    GoldOre,
+    DiamondOre,
+    /// The bark on the sides of logs.
+    Wood,
+    /// The rings on the ends of logs.
+    WoodTop,
+    WoodPlanks,
+    /// The sides of a furnace, with the fire showing.
+    Furnace,
+    /// The top and bottom of a furnace.
+    FurnaceTop,
+    Water,
+    Snow,
+    Ice,
+    Crystal,
+    /// The sides of a furnace with no fire going.
+    UnlitFurnace,
 }
 
 impl BlockTextureIndex {
+    /// Every layer, in order.
+    pub const ALL: [BlockTextureIndex; 22] = [
+        Self::Dirt,
+        Self::Grass,
+        Self::Stone,
+        Self::Sand,
+        Self::Glass,
+        Self::Leaves,
+        Self::Cobblestone,
+        Self::CoalOre,
+        Self::IronOre,
+        Self::CopperOre,
+        Self::GoldOre,
+        Self::DiamondOre,
+        Self::Wood,
+        Self::WoodTop,
+        Self::WoodPlanks,
+        Self::Furnace,
+        Self::FurnaceTop,
+        Self::Water,
+        Self::Snow,
+        Self::Ice,
+        Self::Crystal,
+        Self::UnlitFurnace,
+    ];
+
+    /// Returns the texture on the face of the block type that points in the direction.
     pub fn from_type_and_direction(
         ty: BlockType,
         direction: Direction,
     ) -> Result<Self, NoSuchBlockTextureError> {
-        match direction {
-            Direction::Up => Self::get_top(ty),
-            Direction::Down => Self::get_bottom(ty),
-            _ => Self::get_side(ty),
-        }
-    }
-
-    fn get_top(ty: BlockType) -> Result<Self, NoSuchBlockTextureError> {
-        Ok(match ty {
-            BlockType::Sand => Self::Sand,
-            BlockType::Dirt => Self::Dirt,
-            BlockType::Grass => Self::Grass,
-            BlockType::Stone => Self::Stone,
-            _ => return Err(NoSuchBlockTextureError { for_type: ty }),
+        let faces = ty
+            .definition()
+            .textures
+            .ok_or(NoSuchBlockTextureError { for_type: ty })?;
+        Ok(match direction {
+            Direction::Up => faces.top,
+            Direction::Down => faces.bottom,
+            _ => faces.side,
         })
     }
 
-    fn get_bottom(ty: BlockType) -> Result<Self, NoSuchBlockTextureError> {
-        Ok(match ty {
-            BlockType::Sand => Self::Sand,
-            BlockType::Dirt => Self::Dirt,
-            BlockType::Grass => Self::Dirt,
-            BlockType::Stone => Self::Stone,
-            _ => return Err(NoSuchBlockTextureError { for_type: ty }),
-        })
+    /// Returns the name of the texture's image in `assets/images/blocks`, without its extension.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dirt => "dirt",
+            Self::Grass => "grass",
+            Self::Stone => "stone",
+            Self::Sand => "sand",
+            Self::Glass => "glass",
+            Self::Leaves => "leaves",
+            Self::Cobblestone => "cobblestone",
+            Self::CoalOre => "coal_ore",
+            Self::IronOre => "iron_ore",
+            Self::CopperOre => "copper_ore",
+            Self::GoldOre => "gold_ore",
+            Self::DiamondOre => "diamond_ore",
+            Self::Wood => "wood",
+            Self::WoodTop => "wood_top",
+            Self::WoodPlanks => "wood_planks",
+            Self::Furnace => "furnace",
+            Self::FurnaceTop => "furnace_top",
+            Self::Water => "water",
+            Self::Snow => "snow",
+            Self::Ice => "ice",
+            Self::Crystal => "crystal",
+            Self::UnlitFurnace => "furnace_unlit",
+        }
     }
 
-    fn get_side(ty: BlockType) -> Result<Self, NoSuchBlockTextureError> {
-        Ok(match ty {
-            BlockType::Sand => Self::Sand,
-            BlockType::Dirt => Self::Dirt,
-            BlockType::Grass => Self::Grass,
-            BlockType::Stone => Self::Stone,
-            _ => return Err(NoSuchBlockTextureError { for_type: ty }),
-        })
+    /// Returns the texture with the name, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|texture| texture.name() == name)
     }
 
-    pub fn to_tex_coord(self) -> f32 {
-        (self as u32) as f32 / (BLOCK_TEXTURE_COUNT as f32)
+    /// Returns the built-in image for the layer.
+    fn default_image(self) -> &'static [u8] {
+        match self {
+            Self::Dirt => include_bytes!("../../assets/images/blocks/dirt.png"),
+            Self::Grass => include_bytes!("../../assets/images/blocks/grass.png"),
+            Self::Stone => include_bytes!("../../assets/images/blocks/stone.png"),
+            Self::Sand => include_bytes!("../../assets/images/blocks/sand.png"),
+            Self::Glass => include_bytes!("../../assets/images/blocks/glass.png"),
+            Self::Leaves => include_bytes!("../../assets/images/blocks/leaves.png"),
+            Self::Cobblestone => include_bytes!("../../assets/images/blocks/cobblestone.png"),
+            Self::CoalOre => include_bytes!("../../assets/images/blocks/coal_ore.png"),
+            Self::IronOre => include_bytes!("../../assets/images/blocks/iron_ore.png"),
+            Self::CopperOre => include_bytes!("../../assets/images/blocks/copper_ore.png"),
+            Self::GoldOre => include_bytes!("../../assets/images/blocks/gold_ore.png"),
+            Self::DiamondOre => include_bytes!("../../assets/images/blocks/diamond_ore.png"),
+            Self::Wood => include_bytes!("../../assets/images/blocks/wood.png"),
+            Self::WoodTop => include_bytes!("../../assets/images/blocks/wood_top.png"),
+            Self::WoodPlanks => include_bytes!("../../assets/images/blocks/wood_planks.png"),
+            Self::Furnace => include_bytes!("../../assets/images/blocks/furnace.png"),
+            Self::FurnaceTop => include_bytes!("../../assets/images/blocks/furnace_top.png"),
+            Self::Water => include_bytes!("../../assets/images/blocks/water.png"),
+            Self::Snow => include_bytes!("../../assets/images/blocks/snow.png"),
+            Self::Ice => include_bytes!("../../assets/images/blocks/ice.png"),
+            Self::Crystal => include_bytes!("../../assets/images/blocks/crystal.png"),
+            Self::UnlitFurnace => include_bytes!("../../assets/images/blocks/furnace_unlit.png"),
+        }
     }
 }
 
 impl TryFrom<BlockType> for BlockTextureIndex {
     type Error = NoSuchBlockTextureError;
 
+    /// Returns the texture on the sides of the block type.
     fn try_from(t: BlockType) -> Result<Self, Self::Error> {
-        Ok(match t {
-            BlockType::Dirt => Self::Dirt,
-            BlockType::Grass => Self::Grass,
-            BlockType::Stone => Self::Stone,
-            BlockType::Sand => Self::Sand,
-            _ => return Err(Self::Error { for_type: t }),
-        })
+        Self::from_type_and_direction(t, Direction::North)
     }
 }
 
@@ -95,56 +186,75 @@ impl Display for NoSuchBlockTextureError {
 impl Error for NoSuchBlockTextureError {}
 
 pub struct BlockTextures {
-    pub textures: engine::Texture3d,
+    pub textures: engine::TextureArray,
     bind_group: wgpu::BindGroup,
+    animations: Vec<TextureAnimation>,
 }
 
 impl BlockTextures {
-    pub fn default_textures(
+    /// Makes the block textures out of the resource pack's images, using the built-in image for
+    /// every texture the pack doesn't replace, or for all of them if there's no pack. Every image
+    /// has to be as wide as `texture_dimensions` and a whole number of frames tall.
+    pub fn load(
         device: &wgpu::Device,
+        pack: Option<&ResourcePack>,
         texture_dimensions: (u32, u32),
         block_texture_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> Result<(Self, Vec<wgpu::CommandBuffer>), Box<dyn Error>> {
-        let mut textures = engine::Texture3d::new(
+    ) -> Result<(Self, Vec<wgpu::CommandBuffer>), TextureError> {
+        let frames = BlockTextureIndex::ALL
+            .iter()
+            .map(|&index| Self::load_frames(pack, index, texture_dimensions))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // every texture's first frame is in its own layer, and any other frames come after them
+        let layers = frames
+            .iter()
+            .map(|texture_frames| texture_frames.len() as u32 - 1)
+            .sum::<u32>()
+            + BLOCK_TEXTURE_COUNT;
+        if layers > MAX_BLOCK_TEXTURE_LAYERS {
+            return Err(TextureError::from_message(format!(
+                "block textures have {} frames in all, but there can't be more than {}",
+                layers, MAX_BLOCK_TEXTURE_LAYERS
+            )));
+        }
+
+        let mut textures = engine::TextureArray::new(
             device,
             texture_dimensions,
-            BLOCK_TEXTURE_COUNT,
+            layers,
+            // averaging keeps the overall color of faces far away instead of picking one of
+            // their pixels, which is what makes distant terrain shimmer
+            engine::MipFilter::Box,
             Some("block textures"),
         );
 
-        let dirt_cmd = textures
-            .set_layer_from_bytes(
-                device,
-                BlockTextureIndex::Dirt as u32,
-                include_bytes!("../../assets/images/blocks/dirt.png"),
-            )
-            .map_err(|e| MakeTextureError::new("dirt", e))?;
-
-        let grass_cmd = textures
-            .set_layer_from_bytes(
-                device,
-                BlockTextureIndex::Grass as u32,
-                include_bytes!("../../assets/images/blocks/grass.png"),
-            )
-            .map_err(|e| MakeTextureError::new("grass", e))?;
-
-        let stone_cmd = textures
-            .set_layer_from_bytes(
-                device,
-                BlockTextureIndex::Stone as u32,
-                include_bytes!("../../assets/images/blocks/stone.png"),
-            )
-            .map_err(|e| MakeTextureError::new("stone", e))?;
-
-        let sand_cmd = textures
-            .set_layer_from_bytes(
-                device,
-                BlockTextureIndex::Sand as u32,
-                include_bytes!("../../assets/images/blocks/sand.png"),
-            )
-            .map_err(|e| MakeTextureError::new("sand", e))?;
-
-        let commands = vec![dirt_cmd, stone_cmd, grass_cmd, sand_cmd];
+        let mut commands = Vec::with_capacity(layers as usize);
+        let mut animations = Vec::new();
+        let mut next_layer = BLOCK_TEXTURE_COUNT;
+        for (&index, texture_frames) in BlockTextureIndex::ALL.iter().zip(frames) {
+            let frame_count = texture_frames.len() as u32;
+            if frame_count > 1 {
+                let frame_time = pack
+                    .and_then(|pack| pack.frame_time(index))
+                    .unwrap_or(DEFAULT_FRAME_TIME);
+                animations.push(TextureAnimation::new(
+                    index,
+                    next_layer,
+                    frame_count,
+                    frame_time,
+                ));
+            }
+
+            let mut texture_frames = texture_frames.into_iter();
+            if let Some(first) = texture_frames.next() {
+                commands.push(textures.set_layer_from_image(device, index as u32, first)?);
+            }
+            for frame in texture_frames {
+                commands.push(textures.set_layer_from_image(device, next_layer, frame)?);
+                next_layer += 1;
+            }
+        }
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: block_texture_bind_group_layout,
@@ -165,42 +275,80 @@ impl BlockTextures {
             Self {
                 textures,
                 bind_group,
+                animations,
             },
             commands,
         ))
     }
 
+    /// Returns the texture called `name` if `load` would find an image for it with the pack: the
+    /// pack's image if the pack replaces the texture and the file is there, or else the built-in
+    /// one.
+    pub fn resolve(name: &str, pack: Option<&ResourcePack>) -> Option<BlockTextureIndex> {
+        let texture = BlockTextureIndex::from_name(name)?;
+        match pack.and_then(|pack| pack.image_path(texture)) {
+            Some(path) if !path.is_file() => None,
+            _ => Some(texture),
+        }
+    }
+
     pub fn get_bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
-}
 
-#[derive(Debug)]
-struct MakeTextureError {
-    block_name: String,
-    error: Box<dyn Error>,
-}
-
-impl MakeTextureError {
-    fn new<E: Error + 'static>(block_name: &str, error: E) -> Self {
-        Self {
-            block_name: String::from(block_name),
-            error: Box::new(error),
+    /// Moves every animated texture along by the time since the last update.
+    pub fn update(&mut self, delta_sec: f32) {
+        for animation in &mut self.animations {
+            animation.advance(delta_sec);
         }
     }
-}
 
-impl Display for MakeTextureError {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(
-            f,
-            "error making texture for `{}`: {}",
-            self.block_name, self.error
-        )
+    /// Returns the layer of the texture array with the frame of the texture that's showing.
+    pub fn current_layer(&self, texture: BlockTextureIndex) -> u32 {
+        self.animations
+            .iter()
+            .find(|animation| animation.texture() == texture)
+            .map_or(texture as u32, TextureAnimation::current_layer)
     }
-}
 
-impl Error for MakeTextureError {}
+    /// Loads the image for the texture, from the pack if it has one and from the built-in images
+    /// otherwise, checks its size and splits it into frames. Errors name the file that's wrong.
+    fn load_frames(
+        pack: Option<&ResourcePack>,
+        texture: BlockTextureIndex,
+        texture_dimensions: (u32, u32),
+    ) -> Result<Vec<image::DynamicImage>, TextureError> {
+        let (image, source) = match pack.and_then(|pack| pack.image_path(texture)) {
+            Some(path) => (image::open(path), format!("`{}`", path.display())),
+            None => (
+                image::load_from_memory(texture.default_image()),
+                format!("built-in image `{}.png`", texture.name()),
+            ),
+        };
+        let mut image = image.map_err(|e| {
+            TextureError::from_error_with_detail(e, &format!("couldn't load {}", source))
+        })?;
+
+        let (width, height) = image.dimensions();
+        let (frame_width, frame_height) = texture_dimensions;
+        if width != frame_width || height == 0 || height % frame_height != 0 {
+            return Err(TextureError::from_message(format!(
+                "{} for texture `{}` is {}x{}, but block textures are {}x{}, or a strip of frames \
+                 that size from top to bottom",
+                source,
+                texture.name(),
+                width,
+                height,
+                frame_width,
+                frame_height,
+            )));
+        }
+
+        Ok((0..height / frame_height)
+            .map(|frame| image.crop(0, frame * frame_height, frame_width, frame_height))
+            .collect())
+    }
+}
 
 #[derive(Debug)]
 struct TextureFromBytesError {
@@ -222,3 +370,88 @@ impl Display for TextureFromBytesError {
 }
 
 impl Error for TextureFromBytesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::ChunkMeshVertex;
+
+    /// The textures every block type should have on its top, sides and bottom.
+    fn expected_faces(ty: BlockType) -> Option<[BlockTextureIndex; 3]> {
+        use BlockTextureIndex as Texture;
+
+        let all = |texture| Some([texture; 3]);
+        match ty {
+            BlockType::Air => None,
+            BlockType::Sand => all(Texture::Sand),
+            BlockType::Dirt => all(Texture::Dirt),
+            BlockType::Grass => Some([Texture::Grass, Texture::Grass, Texture::Dirt]),
+            BlockType::Stone => all(Texture::Stone),
+            BlockType::Cobblestone => all(Texture::Cobblestone),
+            BlockType::CoalOre => all(Texture::CoalOre),
+            BlockType::IronOre => all(Texture::IronOre),
+            BlockType::CopperOre => all(Texture::CopperOre),
+            BlockType::GoldOre => all(Texture::GoldOre),
+            BlockType::DiamondOre => all(Texture::DiamondOre),
+            BlockType::Wood => Some([Texture::WoodTop, Texture::Wood, Texture::WoodTop]),
+            BlockType::WoodPlanks => all(Texture::WoodPlanks),
+            BlockType::Furnace => Some([
+                Texture::FurnaceTop,
+                Texture::UnlitFurnace,
+                Texture::FurnaceTop,
+            ]),
+            BlockType::Leaves => all(Texture::Leaves),
+            BlockType::Glass => all(Texture::Glass),
+            BlockType::Water => all(Texture::Water),
+            BlockType::Snow => all(Texture::Snow),
+            BlockType::Ice => all(Texture::Ice),
+            BlockType::Crystal => all(Texture::Crystal),
+            BlockType::LitFurnace => {
+                Some([Texture::FurnaceTop, Texture::Furnace, Texture::FurnaceTop])
+            }
+        }
+    }
+
+    #[test]
+    fn every_block_type_maps_to_its_textures() {
+        for &ty in BlockType::ALL.iter() {
+            for &direction in Direction::ALL.iter() {
+                let texture = BlockTextureIndex::from_type_and_direction(ty, direction).ok();
+                let expected = expected_faces(ty).map(|[top, side, bottom]| match direction {
+                    Direction::Up => top,
+                    Direction::Down => bottom,
+                    _ => side,
+                });
+
+                assert_eq!(texture, expected, "{:?} facing {:?}", ty, direction);
+            }
+        }
+    }
+
+    #[test]
+    fn texture_indices_are_layers_of_the_array() {
+        for (layer, &texture) in BlockTextureIndex::ALL.iter().enumerate() {
+            assert_eq!(texture as usize, layer);
+        }
+        assert_eq!(BLOCK_TEXTURE_COUNT as usize, BlockTextureIndex::ALL.len());
+
+        // every layer has to fit in a mesh vertex
+        let last = BLOCK_TEXTURE_COUNT - 1;
+        let vertex = ChunkMeshVertex::new((0, 0, 0), Direction::Up, 3, last, 0);
+        assert_eq!(vertex.texture_layer(), last);
+    }
+
+    #[test]
+    fn every_texture_has_an_image() {
+        let mut names = Vec::new();
+        for &texture in BlockTextureIndex::ALL.iter() {
+            // animated textures are strips of frames, but every frame is the same size
+            let frames = BlockTextures::load_frames(None, texture, (16, 16))
+                .unwrap_or_else(|e| panic!("{:?}: {}", texture, e));
+            assert!(!frames.is_empty(), "{:?}", texture);
+
+            assert!(!names.contains(&texture.name()), "{:?}", texture);
+            names.push(texture.name());
+        }
+    }
+}
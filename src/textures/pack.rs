@@ -0,0 +1,272 @@
+use super::{BlockTextureIndex, BLOCK_TEXTURE_COUNT};
+use crate::defs::{parse_sections, text, Value};
+use crate::engine::TextureError;
+use std::path::{Path, PathBuf};
+
+/// The file in a resource pack's directory that says what's in the pack.
+pub const MANIFEST_NAME: &str = "pack.defs";
+
+/// A directory of images that replace some or all of the built-in block textures. Its manifest
+/// names the pack and says which image each texture comes from, relative to the directory:
+///
+/// ```text
+/// [pack]
+/// name = "Autumn"
+///
+/// [textures]
+/// grass = "blocks/grass.png"
+/// leaves = "blocks/orange_leaves.png"
+/// water = "blocks/water.png"
+///
+/// [frame_times]
+/// water = 0.5
+/// ```
+///
+/// Textures the manifest doesn't mention keep their built-in images. An image taller than a
+/// texture is a strip of frames from top to bottom, each showing for its texture's frame time in
+/// seconds.
+#[derive(Debug)]
+pub struct ResourcePack {
+    pub name: String,
+
+    /// The image each texture comes from, indexed by layer. None for textures the pack leaves
+    /// alone.
+    images: Vec<Option<PathBuf>>,
+
+    /// How long each frame of each texture shows for, indexed by layer. None for textures that
+    /// keep the default.
+    frame_times: Vec<Option<f32>>,
+}
+
+impl ResourcePack {
+    /// Reads the manifest of the resource pack in the directory. The images themselves aren't
+    /// read until the block textures are made.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Self, TextureError> {
+        let directory = directory.as_ref();
+        let manifest_path = directory.join(MANIFEST_NAME);
+        let manifest = std::fs::read_to_string(&manifest_path).map_err(|e| {
+            TextureError::from_error_with_detail(
+                e,
+                &format!("couldn't read `{}`", manifest_path.display()),
+            )
+        })?;
+
+        Self::parse(directory, &manifest_path, &manifest)
+    }
+
+    fn parse(directory: &Path, manifest_path: &Path, manifest: &str) -> Result<Self, TextureError> {
+        let error = |line: usize, message: String| {
+            TextureError::from_message(format!(
+                "{}, line {}: {}",
+                manifest_path.display(),
+                line,
+                message
+            ))
+        };
+
+        let mut name = None;
+        let mut pack_line = None;
+        let mut images = vec![None; BLOCK_TEXTURE_COUNT as usize];
+        let mut frame_times = vec![None; BLOCK_TEXTURE_COUNT as usize];
+
+        for section in parse_sections(manifest).map_err(|(line, e)| error(line, e))? {
+            if !["pack", "textures", "frame_times"].contains(&section.name.as_str()) {
+                let message = format!("unknown section `{}`", section.name);
+                return Err(error(section.line, message));
+            }
+            if section.name == "pack" {
+                pack_line = pack_line.or(Some(section.line));
+            }
+
+            for (key, value, line) in &section.entries {
+                let line = *line;
+                let set_twice = || error(line, format!("`{}` is set more than once", key));
+
+                if section.name == "pack" {
+                    if key != "name" {
+                        return Err(error(line, format!("unknown key `{}`", key)));
+                    }
+                    if name.is_some() {
+                        return Err(set_twice());
+                    }
+                    let value = text(key, value, line).map_err(|(line, e)| error(line, e))?;
+                    name = Some(value.to_string());
+                    continue;
+                }
+
+                let texture = BlockTextureIndex::from_name(key)
+                    .ok_or_else(|| error(line, format!("no texture named `{}`", key)))?;
+
+                if section.name == "textures" {
+                    let image = &mut images[texture as usize];
+                    if image.is_some() {
+                        return Err(set_twice());
+                    }
+                    let value = text(key, value, line).map_err(|(line, e)| error(line, e))?;
+                    *image = Some(directory.join(value));
+                } else {
+                    let frame_time = &mut frame_times[texture as usize];
+                    if frame_time.is_some() {
+                        return Err(set_twice());
+                    }
+                    *frame_time = Some(match value {
+                        Value::Number(seconds) if *seconds > 0.0 => *seconds,
+                        _ => {
+                            let message =
+                                format!("`{}` has to be a number of seconds above 0", key);
+                            return Err(error(line, message));
+                        }
+                    });
+                }
+            }
+        }
+
+        let name = name.ok_or_else(|| {
+            let message = "the pack needs a `name` under `[pack]`".to_string();
+            match pack_line {
+                Some(line) => error(line, message),
+                None => {
+                    TextureError::from_message(format!("{}: {}", manifest_path.display(), message))
+                }
+            }
+        })?;
+
+        Ok(Self {
+            name,
+            images,
+            frame_times,
+        })
+    }
+
+    /// Returns the image the pack gives the texture, or None if the texture keeps its built-in
+    /// image.
+    pub fn image_path(&self, texture: BlockTextureIndex) -> Option<&Path> {
+        self.images[texture as usize].as_deref()
+    }
+
+    /// Returns how long each frame of the texture shows for, if the pack says.
+    pub fn frame_time(&self, texture: BlockTextureIndex) -> Option<f32> {
+        self.frame_times[texture as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(manifest: &str) -> Result<ResourcePack, TextureError> {
+        let directory = Path::new("packs/autumn");
+        ResourcePack::parse(directory, &directory.join(MANIFEST_NAME), manifest)
+    }
+
+    /// Checks that parsing the manifest fails with an error that points at the line.
+    fn assert_error(manifest: &str, line: usize, message: &str) {
+        let e = parse(manifest).unwrap_err().to_string();
+        let place = format!(
+            "{}, line {}: ",
+            Path::new("packs/autumn/pack.defs").display(),
+            line
+        );
+        assert!(e.starts_with(&place), "{}", e);
+        assert!(e.contains(message), "{}", e);
+    }
+
+    #[test]
+    fn manifests_say_where_images_and_frame_times_come_from() {
+        let pack = parse(
+            "[pack]\n\
+             name = \"Autumn\"\n\
+             [textures]\n\
+             leaves = \"blocks/orange_leaves.png\"\n\
+             [frame_times]\n\
+             leaves = 0.5\n",
+        )
+        .unwrap();
+
+        assert_eq!(pack.name, "Autumn");
+        assert_eq!(
+            pack.image_path(BlockTextureIndex::Leaves),
+            Some(Path::new("packs/autumn/blocks/orange_leaves.png"))
+        );
+        assert_eq!(pack.frame_time(BlockTextureIndex::Leaves), Some(0.5));
+        assert_eq!(pack.image_path(BlockTextureIndex::Stone), None);
+        assert_eq!(pack.frame_time(BlockTextureIndex::Stone), None);
+    }
+
+    #[test]
+    fn unknown_sections_are_rejected() {
+        assert_error(
+            "[pack]\nname = \"Autumn\"\n\n[sounds]\n",
+            4,
+            "unknown section `sounds`",
+        );
+    }
+
+    #[test]
+    fn unknown_textures_are_rejected() {
+        assert_error(
+            "[pack]\nname = \"Autumn\"\n[textures]\nquicksand = \"quicksand.png\"\n",
+            4,
+            "no texture named `quicksand`",
+        );
+        assert_error(
+            "[pack]\nname = \"Autumn\"\n[frame_times]\nquicksand = 1\n",
+            4,
+            "no texture named `quicksand`",
+        );
+    }
+
+    #[test]
+    fn keys_set_twice_are_rejected() {
+        assert_error(
+            "[pack]\nname = \"Autumn\"\nname = \"Winter\"\n",
+            3,
+            "`name` is set more than once",
+        );
+        assert_error(
+            "[pack]\nname = \"Autumn\"\n\
+             [textures]\nsand = \"a.png\"\n\
+             [textures]\nsand = \"b.png\"\n",
+            6,
+            "`sand` is set more than once",
+        );
+        assert_error(
+            "[pack]\nname = \"Autumn\"\n[frame_times]\nwater = 1\nwater = 2\n",
+            5,
+            "`water` is set more than once",
+        );
+    }
+
+    #[test]
+    fn packs_need_a_name() {
+        assert_error(
+            "[pack]\n[textures]\nsand = \"sand.png\"\n",
+            1,
+            "the pack needs a `name` under `[pack]`",
+        );
+
+        // without a `[pack]` section there's no line to point at
+        let e = parse("[textures]\nsand = \"sand.png\"\n")
+            .unwrap_err()
+            .to_string();
+        let path = Path::new("packs/autumn/pack.defs").display().to_string();
+        assert_eq!(
+            e,
+            format!("{}: the pack needs a `name` under `[pack]`", path)
+        );
+    }
+
+    #[test]
+    fn frame_times_have_to_be_above_zero() {
+        for frame_time in ["0", "-0.5", "\"fast\""].iter() {
+            assert_error(
+                &format!(
+                    "[pack]\nname = \"Autumn\"\n[frame_times]\nwater = {}\n",
+                    frame_time
+                ),
+                4,
+                "`water` has to be a number of seconds above 0",
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::items;
+use crate::world::save::{Reader, SaveError};
 
 /// Flammable can be added to objects that can be ignited. Flammables will ignite any neighboring
 /// Flammables as well.
@@ -34,7 +35,7 @@ pub trait Killable {
 
 /// Renderable describes an object that can be rendered.
 pub trait Renderable {
-    fn render(&self, render_pass: &wgpu::RenderPass);
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
 }
 
 /// Logicable describes an object that can be updated, such as an object with physics or an
@@ -42,3 +43,13 @@ pub trait Renderable {
 pub trait Logicable {
     fn logic(&mut self, delta_sec: f32);
 }
+
+/// Persist describes an object that can be written to a world save and read back from it exactly
+/// as it was.
+pub trait Persist: Sized {
+    /// Appends the object's bytes to the end of `bytes`.
+    fn write_to(&self, bytes: &mut Vec<u8>);
+
+    /// Reads back an object written by `write_to`.
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError>;
+}
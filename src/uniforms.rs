@@ -1,4 +1,9 @@
 use crate::camera::Camera;
+use crate::textures::{BlockTextureIndex, BlockTextures, MAX_BLOCK_TEXTURE_LAYERS};
+
+/// How many `uvec4`s the table of block texture layers takes up. Arrays in uniforms are padded so
+/// that every element takes 16 bytes, so the layers are packed four to an element.
+const BLOCK_TEXTURE_LAYER_SLOTS: usize = MAX_BLOCK_TEXTURE_LAYERS as usize / 4;
 
 /// Uniforms are used in the shader for attributes that are essentially global.
 #[repr(C)] // we need this for Rust to store our data correctly for the shaders
@@ -6,13 +11,23 @@ use crate::camera::Camera;
 pub struct Uniforms {
     /// The view-projection matrix.
     pub view_proj: cgmath::Matrix4<f32>,
+
+    /// The layer of the block texture array that each block texture is showing this frame, by
+    /// the texture's own layer. Only animated textures ever show a layer other than their own.
+    pub block_texture_layers: [[u32; 4]; BLOCK_TEXTURE_LAYER_SLOTS],
 }
 
 impl Uniforms {
     pub fn new() -> Self {
         use cgmath::SquareMatrix;
+        let mut block_texture_layers = [[0; 4]; BLOCK_TEXTURE_LAYER_SLOTS];
+        for (layer, slot) in block_texture_layers.iter_mut().flatten().enumerate() {
+            *slot = layer as u32;
+        }
+
         Self {
             view_proj: cgmath::Matrix4::identity(),
+            block_texture_layers,
         }
     }
 
@@ -20,14 +35,23 @@ impl Uniforms {
         self.view_proj = *camera.get_view_projection_matrix();
     }
 
+    fn update_block_texture_layers(&mut self, block_textures: &BlockTextures) {
+        for &texture in BlockTextureIndex::ALL.iter() {
+            let layer = texture as usize;
+            self.block_texture_layers[layer / 4][layer % 4] = block_textures.current_layer(texture);
+        }
+    }
+
     pub fn update(
         &mut self,
         device: &wgpu::Device,
         camera: &Camera,
+        block_textures: &BlockTextures,
         uniform_buffer: &mut wgpu::Buffer,
         queue: &mut wgpu::Queue,
     ) {
         self.update_view_proj(&camera);
+        self.update_block_texture_layers(block_textures);
 
         // Copy operations are performed on the gpu, so we'll need
         // a CommandEncoder for that
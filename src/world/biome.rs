@@ -0,0 +1,258 @@
+use crate::blocks::BlockType;
+use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
+
+/// Added to the elevation noise so that land is a bit more common than ocean.
+const ELEVATION_BIAS: f64 = 0.1;
+
+/// Elevation below which columns are ocean.
+const SHORE_ELEVATION: f64 = 0.0;
+
+/// Elevation at which shores give way to low elevation biomes.
+const LOW_ELEVATION: f64 = 0.08;
+
+/// Elevation at which low elevation biomes give way to medium elevation biomes.
+const MEDIUM_ELEVATION: f64 = 0.25;
+
+/// Elevation at which medium elevation biomes give way to high elevation biomes.
+const HIGH_ELEVATION: f64 = 0.45;
+
+/// Elevation above which cold, high columns become snowy mountains.
+const PEAK_ELEVATION: f64 = 0.6;
+
+/// Temperature and humidity values are split into three bands at these values.
+const LOWER_BAND: f64 = -0.15;
+const UPPER_BAND: f64 = 0.15;
+
+/// Biomes, as listed in biomes.norg.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Biome {
+    ConiferousForest,
+    DeciduousForest,
+    Desert,
+    IceCaps,
+    Mountains,
+    Ocean,
+
+    /// An ocean cold enough to freeze over. The spec calls these "ice flats".
+    FrozenOcean,
+    Plains,
+    Plateaus,
+    Rainforest,
+    RollingHills,
+    SandBeach,
+    SandDunes,
+    Savannah,
+    SnowyMountains,
+    Swamp,
+    Taiga,
+    Tundra,
+}
+
+impl Biome {
+    /// The block that covers the surface of the biome.
+    pub fn surface_block(self) -> BlockType {
+        match self {
+            Biome::Desert | Biome::SandBeach | Biome::SandDunes => BlockType::Sand,
+            Biome::Ocean | Biome::FrozenOcean => BlockType::Sand,
+            Biome::IceCaps | Biome::Tundra | Biome::SnowyMountains => BlockType::Snow,
+            Biome::Mountains | Biome::Plateaus => BlockType::Stone,
+            _ => BlockType::Grass,
+        }
+    }
+
+    /// The block that lies right below the surface of the biome, down to the stone.
+    pub fn subsurface_block(self) -> BlockType {
+        match self {
+            Biome::Desert | Biome::SandBeach | Biome::SandDunes => BlockType::Sand,
+            Biome::Ocean | Biome::FrozenOcean => BlockType::Sand,
+            Biome::IceCaps => BlockType::Ice,
+            Biome::Mountains | Biome::Plateaus | Biome::SnowyMountains => BlockType::Stone,
+            _ => BlockType::Dirt,
+        }
+    }
+
+    /// How far, in blocks, the surface of the biome rolls above and below its base elevation.
+    pub fn height_variation(self) -> f64 {
+        match self {
+            Biome::Ocean | Biome::FrozenOcean => 4.0,
+            Biome::SandBeach | Biome::IceCaps | Biome::Swamp => 1.0,
+            Biome::Plains | Biome::Tundra | Biome::Savannah | Biome::Desert => 2.0,
+            Biome::Taiga | Biome::ConiferousForest | Biome::DeciduousForest => 4.0,
+            Biome::Rainforest => 5.0,
+            Biome::SandDunes => 6.0,
+            Biome::RollingHills => 10.0,
+            Biome::Plateaus => 12.0,
+            Biome::Mountains | Biome::SnowyMountains => 28.0,
+        }
+    }
+
+    /// If Some, the surface of the biome is flattened into steps of this many blocks.
+    pub fn terrace_height(self) -> Option<i64> {
+        match self {
+            Biome::Plateaus => Some(6),
+            _ => None,
+        }
+    }
+}
+
+/// Temperature band of a column.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Temperature {
+    Cold,
+    Warm,
+    Hot,
+}
+
+/// Humidity band of a column.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Humidity {
+    Dry,
+    Moderate,
+    Wet,
+}
+
+/// The climate of a single world column: the raw values of each noise field and the biome they
+/// classify to.
+#[derive(Debug, Copy, Clone)]
+pub struct ColumnClimate {
+    pub biome: Biome,
+
+    /// Base elevation, roughly between -1 and 1. Negative values are below sea level.
+    pub elevation: f64,
+    pub temperature: f64,
+    pub humidity: f64,
+}
+
+/// Samples the elevation, temperature and humidity noise fields and picks biomes from them using
+/// the tables in biomes.norg.
+pub struct BiomeClassifier {
+    elevation_noise: Fbm,
+    temperature_noise: Fbm,
+    humidity_noise: Fbm,
+}
+
+impl BiomeClassifier {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            elevation_noise: Fbm::new()
+                .set_seed(seed.wrapping_add(1))
+                .set_octaves(5)
+                .set_frequency(0.002),
+            temperature_noise: Fbm::new()
+                .set_seed(seed.wrapping_add(2))
+                .set_octaves(2)
+                .set_frequency(0.001),
+            humidity_noise: Fbm::new()
+                .set_seed(seed.wrapping_add(3))
+                .set_octaves(2)
+                .set_frequency(0.0015),
+        }
+    }
+
+    /// Samples all three noise fields for the column at the world block position and classifies
+    /// its biome.
+    pub fn climate_at(&self, x: i64, z: i64) -> ColumnClimate {
+        let point = [x as f64, z as f64];
+        let elevation = self.elevation_noise.get(point) + ELEVATION_BIAS;
+        let temperature = self.temperature_noise.get(point);
+        let humidity = self.humidity_noise.get(point);
+
+        ColumnClimate {
+            biome: classify(elevation, temperature, humidity),
+            elevation,
+            temperature,
+            humidity,
+        }
+    }
+
+    /// Returns the biome of the column at the world block position.
+    pub fn biome_at(&self, x: i64, z: i64) -> Biome {
+        self.climate_at(x, z).biome
+    }
+}
+
+/// Picks a biome from base elevation, temperature and humidity.
+pub fn classify(elevation: f64, temperature: f64, humidity: f64) -> Biome {
+    use Biome::*;
+    use Humidity::*;
+    use Temperature::*;
+
+    let temperature = if temperature < LOWER_BAND {
+        Cold
+    } else if temperature < UPPER_BAND {
+        Warm
+    } else {
+        Hot
+    };
+
+    let humidity = if humidity < LOWER_BAND {
+        Dry
+    } else if humidity < UPPER_BAND {
+        Moderate
+    } else {
+        Wet
+    };
+
+    if elevation < SHORE_ELEVATION {
+        return if temperature == Cold {
+            FrozenOcean
+        } else {
+            Ocean
+        };
+    }
+
+    // snowy mountains aren't in any of the tables, so the coldest peaks get them
+    if elevation >= PEAK_ELEVATION && temperature == Cold {
+        return SnowyMountains;
+    }
+
+    if elevation < LOW_ELEVATION {
+        // shores
+        match (temperature, humidity) {
+            (Cold, Dry) => Tundra,
+            (Cold, Moderate) => IceCaps,
+            (Cold, Wet) => IceCaps,
+            (Warm, Dry) => SandBeach,
+            (Warm, Moderate) => SandBeach,
+            (Warm, Wet) => Plains,
+            (Hot, Dry) => Plains,
+            (Hot, Moderate) => Plains,
+            (Hot, Wet) => Swamp,
+        }
+    } else if elevation < MEDIUM_ELEVATION {
+        // low elevation
+        match (temperature, humidity) {
+            (Cold, Dry) => Tundra,
+            (Cold, Moderate) => Tundra,
+            (Cold, Wet) => Taiga,
+            (Warm, Dry) => Desert,
+            (Warm, Moderate) => Savannah,
+            (Warm, Wet) => Swamp,
+            (Hot, Dry) => Desert,
+            (Hot, Moderate) => Savannah,
+            (Hot, Wet) => Rainforest,
+        }
+    } else if elevation < HIGH_ELEVATION {
+        // medium elevation
+        match (temperature, humidity) {
+            (Cold, Dry) => ConiferousForest,
+            (Cold, Moderate) => ConiferousForest,
+            (Cold, Wet) => Taiga,
+            (Warm, Dry) => RollingHills,
+            (Warm, Moderate) => RollingHills,
+            (Warm, Wet) => DeciduousForest,
+            (Hot, Dry) => SandDunes,
+            (Hot, Moderate) => DeciduousForest,
+            (Hot, Wet) => Rainforest,
+        }
+    } else {
+        // high elevation
+        match (temperature, humidity) {
+            (Cold, _) => ConiferousForest,
+            (Warm, _) => DeciduousForest,
+            (Hot, Dry) => Plateaus,
+            (Hot, Moderate) => Plateaus,
+            (Hot, Wet) => Mountains,
+        }
+    }
+}
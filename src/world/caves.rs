@@ -0,0 +1,154 @@
+use super::terrain::{TerrainColumn, SEA_LEVEL};
+use super::ChunkCoords;
+use crate::blocks::{Block, BlockType, Chunk, CHUNK_BLOCK_WIDTH};
+use noise::{NoiseFn, Perlin, Seedable};
+
+/// How close to zero both tunnel noise fields must be for a block to be carved out. Tunnels form
+/// where the zero surfaces of the two fields cross, which gives long, winding worm-like paths.
+const TUNNEL_THICKNESS: f64 = 0.08;
+
+/// How high the cavern noise must be for a block to be carved into a large open cavern.
+const CAVERN_THRESHOLD: f64 = 0.62;
+
+/// How close to zero the ravine path noise must be for a column to be inside a ravine.
+const RAVINE_WIDTH: f64 = 0.025;
+
+/// How high the ravine presence noise must be for ravines to appear at all, which keeps them
+/// occasional.
+const RAVINE_PRESENCE: f64 = 0.35;
+
+/// Deepest a ravine can cut below the surface, in blocks.
+const RAVINE_DEPTH: f64 = 40.0;
+
+/// Blocks of solid ground left between caves and the sea floor, so oceans don't drain into them.
+const SEA_FLOOR_SEAL: i64 = 4;
+
+/// Carves caves and ravines out of generated terrain. Whether a block is carved depends only on
+/// its world position, so tunnels line up across chunk borders no matter which chunk is generated
+/// first.
+pub struct CaveCarver {
+    tunnel_noise_a: Perlin,
+    tunnel_noise_b: Perlin,
+    cavern_noise: Perlin,
+    ravine_path_noise: Perlin,
+    ravine_presence_noise: Perlin,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            tunnel_noise_a: Perlin::new().set_seed(seed.wrapping_add(10)),
+            tunnel_noise_b: Perlin::new().set_seed(seed.wrapping_add(11)),
+            cavern_noise: Perlin::new().set_seed(seed.wrapping_add(12)),
+            ravine_path_noise: Perlin::new().set_seed(seed.wrapping_add(13)),
+            ravine_presence_noise: Perlin::new().set_seed(seed.wrapping_add(14)),
+        }
+    }
+
+    /// Carves air out of every solid block in the chunk that lies in a cave or ravine. This runs
+    /// after the heightmap fills the chunk and before the ore pass.
+    pub fn carve(
+        &self,
+        chunk: &mut Chunk,
+        coords: ChunkCoords,
+        columns: &[[TerrainColumn; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH],
+    ) {
+        let origin = (
+            coords.0 * CHUNK_BLOCK_WIDTH as i64,
+            coords.1 * CHUNK_BLOCK_WIDTH as i64,
+            coords.2 * CHUNK_BLOCK_WIDTH as i64,
+        );
+
+        for (i, row) in columns.iter().enumerate() {
+            for (k, column) in row.iter().enumerate() {
+                let (x, z) = (origin.0 + i as i64, origin.2 + k as i64);
+
+                // skip whole columns that this chunk doesn't reach the ground of
+                if origin.1 > column.surface_height {
+                    continue;
+                }
+
+                let ravine_floor = self.ravine_floor(x, z, column);
+
+                for j in 0..CHUNK_BLOCK_WIDTH {
+                    let y = origin.1 + j as i64;
+                    if y > column.surface_height {
+                        break;
+                    }
+
+                    let ty = chunk.block_type_at(i, j, k);
+                    if ty == BlockType::Air || ty == BlockType::Water || ty == BlockType::Ice {
+                        continue;
+                    }
+
+                    let in_ravine = ravine_floor.is_some_and(|floor| y > floor);
+                    if in_ravine || self.is_cave(x, y, z, column) {
+                        chunk.set(i, j, k, Block::from(BlockType::Air));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns true if the block at the world block position is carved away by a tunnel, cavern
+    /// or ravine. Like the rest of the carver, this only depends on the position, so it works for
+    /// blocks in chunks that haven't been generated yet.
+    pub fn is_carved(&self, x: i64, y: i64, z: i64, column: &TerrainColumn) -> bool {
+        let in_ravine = self
+            .ravine_floor(x, z, column)
+            .is_some_and(|floor| y > floor);
+        in_ravine || self.is_cave(x, y, z, column)
+    }
+
+    /// Returns true if the block at the world block position lies inside of a tunnel or cavern.
+    pub fn is_cave(&self, x: i64, y: i64, z: i64, column: &TerrainColumn) -> bool {
+        if column.surface_height < SEA_LEVEL && y > column.surface_height - SEA_FLOOR_SEAL {
+            return false;
+        }
+
+        // squash caves vertically so they run sideways more than up and down
+        let tunnel_point = [x as f64 * 0.02, y as f64 * 0.035, z as f64 * 0.02];
+        let a = self.tunnel_noise_a.get(tunnel_point);
+        let b = self.tunnel_noise_b.get(tunnel_point);
+        if a * a + b * b < TUNNEL_THICKNESS * TUNNEL_THICKNESS {
+            return true;
+        }
+
+        // caverns only open up well below the surface
+        if y < column.surface_height - 16 {
+            let cavern_point = [x as f64 * 0.012, y as f64 * 0.024, z as f64 * 0.012];
+            if self.cavern_noise.get(cavern_point) > CAVERN_THRESHOLD {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// If the column is inside a ravine, returns the world height above which the ravine carves
+    /// everything away.
+    pub fn ravine_floor(&self, x: i64, z: i64, column: &TerrainColumn) -> Option<i64> {
+        // ravines under the sea would just flood, so leave those alone
+        if column.surface_height < SEA_LEVEL {
+            return None;
+        }
+
+        let point = [x as f64 * 0.003, z as f64 * 0.003];
+        if self
+            .ravine_presence_noise
+            .get([point[0] * 0.5, point[1] * 0.5])
+            < RAVINE_PRESENCE
+        {
+            return None;
+        }
+
+        let distance_from_center = self.ravine_path_noise.get(point).abs();
+        if distance_from_center >= RAVINE_WIDTH {
+            return None;
+        }
+
+        // deepest in the middle of the ravine, sloping up towards its edges
+        let depth = RAVINE_DEPTH * (1.0 - distance_from_center / RAVINE_WIDTH).sqrt();
+        Some(column.surface_height - depth as i64)
+    }
+}
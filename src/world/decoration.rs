@@ -0,0 +1,221 @@
+use super::biome::Biome;
+use super::terrain::{mix_seed, TerrainColumn};
+use super::ChunkCoords;
+use crate::blocks::{Block, BlockType, Chunk, CHUNK_BLOCK_WIDTH};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A block that decoration wants to place somewhere outside of the chunk being generated. It's
+/// held by the world until the chunk it belongs to is generated.
+#[derive(Debug, Copy, Clone)]
+pub struct PendingBlock {
+    /// World block position.
+    pub position: (i64, i64, i64),
+    pub block_type: BlockType,
+}
+
+/// Shapes of trees that decoration can grow.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TreeKind {
+    /// Tall and narrow with cone-shaped layers of leaves, for cold forests.
+    Conifer,
+
+    /// A round crown of leaves on a short trunk, for temperate forests.
+    Broadleaf,
+
+    /// A tall trunk under a wide crown, for rainforests.
+    Jungle,
+
+    /// A short trunk under a flat, wide canopy, for savannahs.
+    Acacia,
+
+    /// A small clump of leaves sitting on the ground.
+    Bush,
+}
+
+impl Biome {
+    /// Returns the chance that a grassy column in the biome grows each kind of tree, in the order
+    /// they should be tried.
+    fn vegetation(self) -> &'static [(TreeKind, f32)] {
+        match self {
+            Biome::Taiga => &[(TreeKind::Conifer, 0.03)],
+            Biome::ConiferousForest => &[(TreeKind::Conifer, 0.04)],
+            Biome::DeciduousForest => &[(TreeKind::Broadleaf, 0.035), (TreeKind::Bush, 0.01)],
+            Biome::Rainforest => &[
+                (TreeKind::Jungle, 0.02),
+                (TreeKind::Broadleaf, 0.02),
+                (TreeKind::Bush, 0.04),
+            ],
+            Biome::Swamp => &[(TreeKind::Broadleaf, 0.01), (TreeKind::Bush, 0.02)],
+            Biome::Savannah => &[(TreeKind::Acacia, 0.004), (TreeKind::Bush, 0.004)],
+            Biome::Plains => &[(TreeKind::Broadleaf, 0.001), (TreeKind::Bush, 0.004)],
+            Biome::RollingHills => &[(TreeKind::Broadleaf, 0.004), (TreeKind::Bush, 0.004)],
+            _ => &[],
+        }
+    }
+}
+
+/// Grows trees and other vegetation on the surface of a freshly generated chunk. Blocks that land
+/// inside of the chunk are placed right away; the rest are returned so the world can place them
+/// in neighboring chunks, which keeps trees near chunk edges whole.
+pub fn decorate(
+    chunk: &mut Chunk,
+    seed: i64,
+    coords: ChunkCoords,
+    columns: &[[TerrainColumn; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH],
+) -> Vec<PendingBlock> {
+    let width = CHUNK_BLOCK_WIDTH as i64;
+    let origin = (coords.0 * width, coords.1 * width, coords.2 * width);
+
+    let mut rng = StdRng::seed_from_u64(mix_seed(seed, coords, 0x7472_6565));
+    let mut placements = Vec::new();
+
+    for (i, row) in columns.iter().enumerate() {
+        for (k, column) in row.iter().enumerate() {
+            // trees grow from the chunk that holds the ground they're rooted in
+            let ground_j = column.surface_height - origin.1;
+            if ground_j < 0 || ground_j >= width {
+                continue;
+            }
+
+            // the ground might have been carved away by a cave or ravine
+            if chunk.block_type_at(i, ground_j as usize, k) != BlockType::Grass {
+                continue;
+            }
+
+            for &(kind, chance) in column.biome.vegetation() {
+                if rng.gen::<f32>() < chance {
+                    let base = (
+                        origin.0 + i as i64,
+                        column.surface_height + 1,
+                        origin.2 + k as i64,
+                    );
+                    grow_tree(kind, base, &mut rng, &mut placements);
+                    break;
+                }
+            }
+        }
+    }
+
+    // place what fits and hand back what doesn't
+    let mut overflow = Vec::new();
+    for pending in placements {
+        let (x, y, z) = pending.position;
+        let (i, j, k) = (x - origin.0, y - origin.1, z - origin.2);
+        if (0..width).contains(&i) && (0..width).contains(&j) && (0..width).contains(&k) {
+            place_if_free(
+                chunk,
+                i as usize,
+                j as usize,
+                k as usize,
+                pending.block_type,
+            );
+        } else {
+            overflow.push(pending);
+        }
+    }
+
+    overflow
+}
+
+/// Places the block at the array position if it won't overwrite anything. Trunks may grow through
+/// leaves, but nothing else is replaced.
+pub fn place_if_free(chunk: &mut Chunk, i: usize, j: usize, k: usize, block_type: BlockType) {
+    let existing = chunk.block_type_at(i, j, k);
+    let is_free = existing == BlockType::Air
+        || (existing == BlockType::Leaves && block_type == BlockType::Wood);
+
+    if is_free {
+        chunk.set(i, j, k, Block::from(block_type));
+    }
+}
+
+/// Adds the blocks of a tree rooted at `base`, which is the world block position right above the
+/// ground.
+fn grow_tree(
+    kind: TreeKind,
+    base: (i64, i64, i64),
+    rng: &mut StdRng,
+    placements: &mut Vec<PendingBlock>,
+) {
+    let (x, y, z) = base;
+    let mut put = |dx: i64, dy: i64, dz: i64, block_type: BlockType| {
+        placements.push(PendingBlock {
+            position: (x + dx, y + dy, z + dz),
+            block_type,
+        });
+    };
+
+    let trunk_height: i64 = match kind {
+        TreeKind::Conifer => rng.gen_range(6, 10),
+        TreeKind::Broadleaf => rng.gen_range(4, 7),
+        TreeKind::Jungle => rng.gen_range(9, 14),
+        TreeKind::Acacia => rng.gen_range(3, 5),
+        TreeKind::Bush => 0,
+    };
+
+    // leaves first so the trunk can replace any that overlap it
+    match kind {
+        TreeKind::Conifer => {
+            // layers of leaves that shrink towards the top, ending in a point above the trunk
+            let lowest = 2;
+            for dy in lowest..=trunk_height {
+                let radius = ((trunk_height - dy) / 2 + 1).min(3);
+                let radius = if (dy - lowest) % 2 == 1 {
+                    radius - 1
+                } else {
+                    radius
+                };
+                for dx in -radius..=radius {
+                    for dz in -radius..=radius {
+                        if dx.abs() + dz.abs() <= radius + 1 {
+                            put(dx, dy, dz, BlockType::Leaves);
+                        }
+                    }
+                }
+            }
+            put(0, trunk_height + 1, 0, BlockType::Leaves);
+        }
+        TreeKind::Broadleaf | TreeKind::Jungle => {
+            let radius: i64 = if kind == TreeKind::Jungle { 3 } else { 2 };
+            let center = trunk_height;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    for dz in -radius..=radius {
+                        // rough sphere; randomly drop some corner leaves so crowns aren't all
+                        // identical
+                        let d = dx * dx + dy * dy + dz * dz;
+                        let r = radius * radius;
+                        if d < r || (d <= r + 1 && rng.gen::<bool>()) {
+                            put(dx, center + dy, dz, BlockType::Leaves);
+                        }
+                    }
+                }
+            }
+        }
+        TreeKind::Acacia => {
+            for dx in -3i64..=3 {
+                for dz in -3i64..=3 {
+                    if dx.abs() + dz.abs() <= 4 {
+                        put(dx, trunk_height, dz, BlockType::Leaves);
+                    }
+                    if dx.abs() + dz.abs() <= 2 {
+                        put(dx, trunk_height + 1, dz, BlockType::Leaves);
+                    }
+                }
+            }
+        }
+        TreeKind::Bush => {
+            for dx in -1i64..=1 {
+                for dz in -1i64..=1 {
+                    put(dx, 0, dz, BlockType::Leaves);
+                }
+            }
+            put(0, 1, 0, BlockType::Leaves);
+        }
+    }
+
+    for dy in 0..trunk_height {
+        put(0, dy, 0, BlockType::Wood);
+    }
+}
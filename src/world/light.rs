@@ -0,0 +1,600 @@
+//! Block light and sky light.
+//!
+//! Every block has two light levels from 0 to `MAX_LIGHT`: sky light, which comes straight down
+//! from the sky without fading and spreads sideways from there, and block light, which spreads out
+//! from blocks that glow, like furnaces. Both spread to the blocks around them by flood fill,
+//! losing a level with every step, and are stopped by blocks that light can't get through.
+//!
+//! Light is worked out for a whole chunk when it's loaded, and only around the blocks that change
+//! after that; `Chunk::set` remembers where light needs updating, and `update` catches up on it.
+
+use super::{ChunkCoords, World};
+use crate::blocks::{BlockType, Chunk, CHUNK_BLOCK_WIDTH};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The brightest light level. Sky light in the open is always this bright.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Packed light levels of a block out in the open, with full sky light and no block light.
+pub const OPEN_SKY: u8 = MAX_LIGHT << 4;
+
+/// Number of blocks in a chunk.
+const BLOCK_COUNT: usize = CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH;
+
+/// The two kinds of light a block can be lit by.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    fn shift(self) -> u32 {
+        match self {
+            LightChannel::Sky => 4,
+            LightChannel::Block => 0,
+        }
+    }
+}
+
+/// The light levels of every block in a chunk, with sky light and block light packed into one
+/// byte per block. Chunks that are lit the same all the way through, like chunks of open sky or
+/// solid rock, store a single level instead.
+#[derive(Debug, Clone)]
+pub struct LightStorage {
+    /// The packed levels of every block, while `levels` is empty.
+    uniform: u8,
+
+    /// Packed levels, in `blocks[i][j][k]` order. Empty if every block has the uniform levels.
+    levels: Vec<u8>,
+}
+
+impl Default for LightStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LightStorage {
+    /// Makes storage with no light at all.
+    pub fn new() -> Self {
+        Self {
+            uniform: 0,
+            levels: Vec::new(),
+        }
+    }
+
+    /// Returns the level of the light channel at the array position.
+    pub fn get(&self, i: usize, j: usize, k: usize, channel: LightChannel) -> u8 {
+        (self.packed(i, j, k) >> channel.shift()) & MAX_LIGHT
+    }
+
+    /// Returns both light levels at the array position, sky light in the high four bits and block
+    /// light in the low four.
+    pub fn packed(&self, i: usize, j: usize, k: usize) -> u8 {
+        if self.levels.is_empty() {
+            self.uniform
+        } else {
+            self.levels[Self::block_index(i, j, k)]
+        }
+    }
+
+    /// Sets the level of the light channel at the array position.
+    pub fn set(&mut self, i: usize, j: usize, k: usize, channel: LightChannel, level: u8) {
+        let shift = channel.shift();
+        let mask = MAX_LIGHT << shift;
+        let packed = self.packed(i, j, k);
+        let new_packed = (packed & !mask) | (level.min(MAX_LIGHT) << shift);
+        if packed == new_packed {
+            return;
+        }
+
+        if self.levels.is_empty() {
+            self.levels = vec![self.uniform; BLOCK_COUNT];
+        }
+        self.levels[Self::block_index(i, j, k)] = new_packed;
+    }
+
+    /// Goes back to storing a single level if every block is lit the same.
+    pub fn compact(&mut self) {
+        if let Some(&first) = self.levels.first() {
+            if self.levels.iter().all(|&packed| packed == first) {
+                self.uniform = first;
+                self.levels = Vec::new();
+            }
+        }
+    }
+
+    fn block_index(i: usize, j: usize, k: usize) -> usize {
+        (i * CHUNK_BLOCK_WIDTH + j) * CHUNK_BLOCK_WIDTH + k
+    }
+}
+
+/// Works out the light of a chunk that was just added to the chunks, along with the light it lets
+/// into, or takes away from, the chunks around it.
+pub fn light_new_chunk(chunks: &mut HashMap<ChunkCoords, Chunk>, coords: ChunkCoords) {
+    let mut lighter = Lighter::new(chunks);
+    let width = CHUNK_BLOCK_WIDTH as i64;
+    let origin = (coords.0 * width, coords.1 * width, coords.2 * width);
+
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+
+    // sky light falls straight down each column until something stops it. columns under a chunk
+    // that isn't loaded yet are taken to be open to the sky; if they aren't, the light is taken
+    // away again when that chunk loads.
+    for x in origin.0..origin.0 + width {
+        for z in origin.2..origin.2 + width {
+            let above = (x, origin.1 + width, z);
+            if lighter.get(above, LightChannel::Sky).unwrap_or(MAX_LIGHT) != MAX_LIGHT {
+                continue;
+            }
+
+            for y in (origin.1..origin.1 + width).rev() {
+                if !lighter.lets_light_through((x, y, z)) {
+                    break;
+                }
+                lighter.set((x, y, z), LightChannel::Sky, MAX_LIGHT);
+                sky_queue.push_back((x, y, z));
+            }
+        }
+    }
+
+    // the columns below this chunk were lit as if it were open sky, which they might not be
+    let mut sky_removal = VecDeque::new();
+    for x in origin.0..origin.0 + width {
+        for z in origin.2..origin.2 + width {
+            let below = (x, origin.1 - 1, z);
+            let bottom = (x, origin.1, z);
+            if lighter.get(below, LightChannel::Sky) == Some(MAX_LIGHT)
+                && lighter.get(bottom, LightChannel::Sky) != Some(MAX_LIGHT)
+            {
+                lighter.set(below, LightChannel::Sky, 0);
+                sky_removal.push_back((below, MAX_LIGHT));
+            }
+        }
+    }
+    sky_queue.extend(lighter.remove(LightChannel::Sky, sky_removal));
+
+    // most chunks have nothing that glows, so don't look through every block unless one does
+    let glows = lighter.chunks[&coords]
+        .storage()
+        .block_types()
+        .any(|ty| ty.light_emission() > 0);
+    if glows {
+        for x in origin.0..origin.0 + width {
+            for y in origin.1..origin.1 + width {
+                for z in origin.2..origin.2 + width {
+                    let emission = lighter
+                        .block_type((x, y, z))
+                        .map_or(0, |ty| ty.light_emission());
+                    if emission > 0 {
+                        lighter.set((x, y, z), LightChannel::Block, emission);
+                        block_queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+    }
+
+    // light already in the chunks around this one spreads into it
+    for a in 0..width {
+        for b in 0..width {
+            let outside = [
+                (-1, a, b),
+                (width, a, b),
+                (a, -1, b),
+                (a, width, b),
+                (a, b, -1),
+                (a, b, width),
+            ];
+            for &(di, dj, dk) in outside.iter() {
+                let position = (origin.0 + di, origin.1 + dj, origin.2 + dk);
+                if lighter
+                    .get(position, LightChannel::Sky)
+                    .is_some_and(|l| l > 1)
+                {
+                    sky_queue.push_back(position);
+                }
+                if lighter
+                    .get(position, LightChannel::Block)
+                    .is_some_and(|l| l > 1)
+                {
+                    block_queue.push_back(position);
+                }
+            }
+        }
+    }
+
+    lighter.spread(LightChannel::Sky, sky_queue);
+    lighter.spread(LightChannel::Block, block_queue);
+    lighter.finish();
+
+    if let Some(chunk) = chunks.get_mut(&coords) {
+        chunk.light_mut().compact();
+        chunk.mark_lit();
+    }
+}
+
+/// Updates the light around every block that changed since the last update.
+pub fn update(chunks: &mut HashMap<ChunkCoords, Chunk>) {
+    let width = CHUNK_BLOCK_WIDTH as i64;
+    let mut changed = Vec::new();
+    for (&coords, chunk) in chunks.iter_mut() {
+        for (i, j, k) in chunk.take_light_updates() {
+            changed.push((
+                coords.0 * width + i as i64,
+                coords.1 * width + j as i64,
+                coords.2 * width + k as i64,
+            ));
+        }
+    }
+
+    if changed.is_empty() {
+        return;
+    }
+
+    let mut lighter = Lighter::new(chunks);
+    for position in changed {
+        for &channel in LightChannel::ALL.iter() {
+            let old_level = match lighter.get(position, channel) {
+                Some(level) => level,
+                None => continue,
+            };
+
+            // take away the light the block had, then let the light around it back in
+            lighter.set(position, channel, 0);
+            let mut removal = VecDeque::new();
+            removal.push_back((position, old_level));
+            let mut queue = lighter.remove(channel, removal);
+
+            if channel == LightChannel::Block {
+                let emission = lighter
+                    .block_type(position)
+                    .map_or(0, |ty| ty.light_emission());
+                if emission > 0 {
+                    lighter.set(position, channel, emission);
+                    queue.push_back(position);
+                }
+            }
+
+            lighter.spread(channel, queue);
+        }
+    }
+    lighter.finish();
+}
+
+/// A world block position.
+type BlockPosition = (i64, i64, i64);
+
+/// Spreads and takes away light across loaded chunks, keeping track of which chunks' meshes it
+/// changes.
+struct Lighter<'a> {
+    chunks: &'a mut HashMap<ChunkCoords, Chunk>,
+
+    /// Chunks whose meshes show light that changed, including the chunks next to changed blocks
+    /// on chunk borders.
+    changed: HashSet<ChunkCoords>,
+}
+
+impl<'a> Lighter<'a> {
+    fn new(chunks: &'a mut HashMap<ChunkCoords, Chunk>) -> Self {
+        Self {
+            chunks,
+            changed: HashSet::new(),
+        }
+    }
+
+    /// Returns the light level at the position, or None if its chunk isn't loaded.
+    fn get(&self, position: BlockPosition, channel: LightChannel) -> Option<u8> {
+        let (coords, (i, j, k)) = World::split_block_position(position.0, position.1, position.2);
+        self.chunks
+            .get(&coords)
+            .map(|chunk| chunk.light().get(i, j, k, channel))
+    }
+
+    fn block_type(&self, position: BlockPosition) -> Option<BlockType> {
+        let (coords, (i, j, k)) = World::split_block_position(position.0, position.1, position.2);
+        self.chunks
+            .get(&coords)
+            .map(|chunk| chunk.block_type_at(i, j, k))
+    }
+
+    fn lets_light_through(&self, position: BlockPosition) -> bool {
+        self.block_type(position)
+            .is_some_and(|ty| ty.lets_light_through())
+    }
+
+    fn set(&mut self, position: BlockPosition, channel: LightChannel, level: u8) {
+        let (coords, (i, j, k)) = World::split_block_position(position.0, position.1, position.2);
+        let chunk = match self.chunks.get_mut(&coords) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        chunk.light_mut().set(i, j, k, channel, level);
+
+        // the faces of blocks in the next chunk over are lit by blocks on this chunk's border
+        self.changed.insert(coords);
+        let last = CHUNK_BLOCK_WIDTH - 1;
+        for (n, offset) in [(i, (1, 0, 0)), (j, (0, 1, 0)), (k, (0, 0, 1))].iter() {
+            if *n == 0 {
+                self.changed.insert((
+                    coords.0 - offset.0,
+                    coords.1 - offset.1,
+                    coords.2 - offset.2,
+                ));
+            } else if *n == last {
+                self.changed.insert((
+                    coords.0 + offset.0,
+                    coords.1 + offset.1,
+                    coords.2 + offset.2,
+                ));
+            }
+        }
+    }
+
+    /// Returns the level that light at `level` has after moving one block by the offset. Sky
+    /// light going straight down doesn't fade.
+    fn spread_level(channel: LightChannel, level: u8, offset: BlockPosition) -> u8 {
+        if channel == LightChannel::Sky && level == MAX_LIGHT && offset == (0, -1, 0) {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+
+    /// Flood fills light outwards from every position in the queue.
+    fn spread(&mut self, channel: LightChannel, mut queue: VecDeque<BlockPosition>) {
+        while let Some(position) = queue.pop_front() {
+            let level = match self.get(position, channel) {
+                Some(level) if level > 1 => level,
+                _ => continue,
+            };
+
+            for offset in NEIGHBOR_OFFSETS.iter() {
+                let next = (
+                    position.0 + offset.0,
+                    position.1 + offset.1,
+                    position.2 + offset.2,
+                );
+                let next_level = Self::spread_level(channel, level, *offset);
+
+                if self.get(next, channel).is_some_and(|l| l < next_level)
+                    && self.lets_light_through(next)
+                {
+                    self.set(next, channel, next_level);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Takes away light that came from the positions in the queue, each with the level it had
+    /// before it was cleared. Returns the positions around the darkened area that are lit from
+    /// somewhere else, which should be spread from again to fill it back in.
+    fn remove(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<(BlockPosition, u8)>,
+    ) -> VecDeque<BlockPosition> {
+        let mut refill = VecDeque::new();
+
+        while let Some((position, level)) = queue.pop_front() {
+            for offset in NEIGHBOR_OFFSETS.iter() {
+                let next = (
+                    position.0 + offset.0,
+                    position.1 + offset.1,
+                    position.2 + offset.2,
+                );
+                let next_level = match self.get(next, channel) {
+                    Some(next_level) => next_level,
+                    None => continue,
+                };
+
+                let came_from_here = next_level != 0
+                    && (next_level < level
+                        || Self::spread_level(channel, level, *offset) == MAX_LIGHT
+                            && next_level == MAX_LIGHT);
+
+                if came_from_here {
+                    self.set(next, channel, 0);
+                    queue.push_back((next, next_level));
+
+                    // blocks that glow keep their own light
+                    if channel == LightChannel::Block {
+                        let emission = self.block_type(next).map_or(0, |ty| ty.light_emission());
+                        if emission > 0 {
+                            self.set(next, channel, emission);
+                            refill.push_back(next);
+                        }
+                    }
+                } else if next_level >= level {
+                    refill.push_back(next);
+                }
+            }
+        }
+
+        refill
+    }
+
+    /// Marks the meshes of every chunk that the light changed in as out of date.
+    fn finish(self) {
+        for coords in self.changed {
+            if let Some(chunk) = self.chunks.get_mut(&coords) {
+                chunk.request_mesh_update();
+            }
+        }
+    }
+}
+
+/// Steps to the six blocks that share a face with a block.
+const NEIGHBOR_OFFSETS: [BlockPosition; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::Block;
+
+    /// Adds an empty chunk at each of the chunk coordinates and lights it, in order.
+    fn empty_chunks(coords: &[ChunkCoords]) -> HashMap<ChunkCoords, Chunk> {
+        let mut chunks = HashMap::new();
+        for &c in coords {
+            add_chunk(&mut chunks, c, Chunk::empty(c.0, c.1, c.2));
+        }
+        chunks
+    }
+
+    fn add_chunk(chunks: &mut HashMap<ChunkCoords, Chunk>, coords: ChunkCoords, mut chunk: Chunk) {
+        // blocks set before the chunk is added are lit by `light_new_chunk`, not `update`
+        chunk.take_light_updates();
+        chunks.insert(coords, chunk);
+        light_new_chunk(chunks, coords);
+    }
+
+    fn set_block(chunks: &mut HashMap<ChunkCoords, Chunk>, position: BlockPosition, ty: BlockType) {
+        let (coords, (i, j, k)) = World::split_block_position(position.0, position.1, position.2);
+        chunks
+            .get_mut(&coords)
+            .unwrap()
+            .set(i, j, k, Block::from(ty));
+        update(chunks);
+    }
+
+    fn light_at(
+        chunks: &HashMap<ChunkCoords, Chunk>,
+        position: BlockPosition,
+        channel: LightChannel,
+    ) -> u8 {
+        let (coords, (i, j, k)) = World::split_block_position(position.0, position.1, position.2);
+        chunks[&coords].light().get(i, j, k, channel)
+    }
+
+    /// Returns every block position in the chunk.
+    fn positions_in(coords: ChunkCoords) -> impl Iterator<Item = BlockPosition> {
+        let width = CHUNK_BLOCK_WIDTH as i64;
+        (0..width * width * width).map(move |n| {
+            (
+                coords.0 * width + n / (width * width),
+                coords.1 * width + n / width % width,
+                coords.2 * width + n % width,
+            )
+        })
+    }
+
+    #[test]
+    fn light_spreads_from_a_placed_source_and_goes_when_it_is_removed() {
+        let mut chunks = empty_chunks(&[(0, 0, 0), (1, 0, 0)]);
+        let emission = BlockType::LitFurnace.light_emission();
+        let furnace = (29, 16, 16);
+
+        set_block(&mut chunks, furnace, BlockType::LitFurnace);
+        assert_eq!(light_at(&chunks, furnace, LightChannel::Block), emission);
+        for step in 1..=emission as i64 {
+            // along the row of blocks from the furnace into the next chunk
+            let position = (furnace.0 + step, furnace.1, furnace.2);
+            let expected = emission.saturating_sub(step as u8);
+            assert_eq!(
+                light_at(&chunks, position, LightChannel::Block),
+                expected,
+                "{} blocks away",
+                step
+            );
+        }
+
+        set_block(&mut chunks, furnace, BlockType::Air);
+        for &coords in &[(0, 0, 0), (1, 0, 0)] {
+            for position in positions_in(coords) {
+                assert_eq!(light_at(&chunks, position, LightChannel::Block), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn plain_furnaces_dont_glow() {
+        let mut chunks = empty_chunks(&[(0, 0, 0)]);
+        set_block(&mut chunks, (16, 16, 16), BlockType::Furnace);
+        assert_eq!(light_at(&chunks, (17, 16, 16), LightChannel::Block), 0);
+    }
+
+    #[test]
+    fn occluders_shade_the_chunk_below_until_they_are_removed() {
+        let mut chunks = empty_chunks(&[(0, 0, 0), (0, -1, 0)]);
+        let stone = (5, 2, 5);
+        let below_border = (5, -10, 5);
+        assert_eq!(
+            light_at(&chunks, below_border, LightChannel::Sky),
+            MAX_LIGHT
+        );
+
+        // the column under the stone is only lit from the side now, one level down
+        set_block(&mut chunks, stone, BlockType::Stone);
+        assert_eq!(light_at(&chunks, stone, LightChannel::Sky), 0);
+        assert_eq!(
+            light_at(&chunks, (5, 1, 5), LightChannel::Sky),
+            MAX_LIGHT - 1
+        );
+        assert_eq!(
+            light_at(&chunks, below_border, LightChannel::Sky),
+            MAX_LIGHT - 1
+        );
+        assert_eq!(light_at(&chunks, (6, -10, 5), LightChannel::Sky), MAX_LIGHT);
+
+        set_block(&mut chunks, stone, BlockType::Air);
+        assert_eq!(light_at(&chunks, stone, LightChannel::Sky), MAX_LIGHT);
+        assert_eq!(
+            light_at(&chunks, below_border, LightChannel::Sky),
+            MAX_LIGHT
+        );
+    }
+
+    #[test]
+    fn glowing_blocks_light_loaded_chunks_next_to_theirs() {
+        let mut chunks = empty_chunks(&[(0, 0, 0)]);
+        let emission = BlockType::LitFurnace.light_emission();
+
+        let mut glowing = Chunk::empty(1, 0, 0);
+        glowing.set(0, 16, 16, Block::from(BlockType::LitFurnace));
+        add_chunk(&mut chunks, (1, 0, 0), glowing);
+
+        assert_eq!(
+            light_at(&chunks, (31, 16, 16), LightChannel::Block),
+            emission - 1
+        );
+        assert_eq!(
+            light_at(&chunks, (28, 16, 16), LightChannel::Block),
+            emission - 4
+        );
+    }
+
+    #[test]
+    fn sky_light_goes_when_a_solid_chunk_loads_above() {
+        let mut chunks = empty_chunks(&[(0, 0, 0)]);
+        assert_eq!(light_at(&chunks, (5, 5, 5), LightChannel::Sky), MAX_LIGHT);
+
+        let width = CHUNK_BLOCK_WIDTH;
+        let mut roof = Chunk::empty(0, 1, 0);
+        for n in 0..width * width * width {
+            let (i, j, k) = (n / (width * width), n / width % width, n % width);
+            roof.set(i, j, k, Block::from(BlockType::Stone));
+        }
+        add_chunk(&mut chunks, (0, 1, 0), roof);
+
+        for position in positions_in((0, 0, 0)) {
+            assert_eq!(
+                light_at(&chunks, position, LightChannel::Sky),
+                0,
+                "{:?}",
+                position
+            );
+        }
+    }
+}
@@ -1,61 +1,542 @@
 #![allow(dead_code)]
 
+pub mod biome;
+pub mod caves;
 mod climate;
+pub mod decoration;
+pub mod light;
+pub mod ores;
+pub mod save;
+pub mod terrain;
 
-use crate::blocks::Chunk;
-use crate::textures::BlockTextures;
-use std::collections::VecDeque;
+use crate::blocks::{
+    Block, BlockType, Chunk, ChunkNeighbors, LevelOfDetail, MeshWorkers, CHUNK_BLOCK_WIDTH,
+    CHUNK_SIZE,
+};
+use crate::maths::Frustum;
+use decoration::PendingBlock;
+use save::{SaveError, WorldSave};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use terrain::TerrainGenerator;
 
-// NOTE: potentially allowing infinite worlds
-// const MAX_WORLD_SIZE: i32 = 1024; // in chunks. 1024 yields thousands upon thousands of blocks in each direction
+/// How much time the world may spend loading chunks each frame, counting everything that goes
+/// with a chunk coming in: generating or loading it, lighting it and asking its neighbors to
+/// remesh. At least one chunk is always loaded per frame if any are queued, so streaming can't
+/// stall completely.
+const CHUNK_LOAD_BUDGET: Duration = Duration::from_millis(4);
 
-/// A world.
+/// Chunk grid coordinates. Chunk (0, 0, 0) starts at the world origin; chunk (1, 0, 0) starts
+/// `CHUNK_BLOCK_WIDTH` blocks away from it along the x axis, and so on.
+pub type ChunkCoords = (i64, i64, i64);
+
+/// A world. Chunks are stored in a map keyed by their chunk coordinates, so the world can grow
+/// in any direction without limit.
 pub struct World {
-    chunks: VecDeque<VecDeque<VecDeque<Chunk>>>,
-    seed: i64,
+    chunks: HashMap<ChunkCoords, Chunk>,
+    generator: TerrainGenerator,
+
+    /// Radius, in chunks, around the streaming center in which chunks are kept loaded.
     render_distance: i32,
-    textures: BlockTextures,
+
+    /// The chunk that streaming is currently centered on. None until the first call to
+    /// `stream_around`.
+    stream_center: Option<ChunkCoords>,
+
+    /// Chunks waiting to be loaded, nearest to the stream center first.
+    load_queue: VecDeque<ChunkCoords>,
+
+    /// A running average of how long loading one queued chunk takes, used to stop before
+    /// starting a chunk that would run past `CHUNK_LOAD_BUDGET`.
+    chunk_load_time: Duration,
+
+    /// Blocks that decoration placed across chunk borders, keyed by the chunk they belong to and
+    /// then by their index within it. They're kept until their chunk is written to the save, so a
+    /// chunk that has to be generated again gets them back, and a block that's already here isn't
+    /// placed a second time when the chunk it grew from is generated again.
+    pending_blocks: HashMap<ChunkCoords, HashMap<(usize, usize, usize), BlockType>>,
+
+    /// Where chunks are saved to and loaded from. None for worlds that only live in memory.
+    save: Option<WorldSave>,
+
+    mesh_workers: MeshWorkers,
+
+    /// The chunk version that each queued mesh job was made from, by chunk.
+    meshing: HashMap<ChunkCoords, u64>,
 }
 
 impl World {
-    fn new(seed: i64, textures: BlockTextures) -> Self {
+    pub fn new(seed: i64) -> Self {
         Self {
-            chunks: Default::default(),
-            seed,
+            chunks: HashMap::new(),
+            generator: TerrainGenerator::new(seed),
             render_distance: 6,
-            textures,
+            stream_center: None,
+            load_queue: VecDeque::new(),
+            chunk_load_time: Duration::from_millis(0),
+            pending_blocks: HashMap::new(),
+            save: None,
+            mesh_workers: MeshWorkers::new(),
+            meshing: HashMap::new(),
+        }
+    }
+
+    /// Makes a new world that is saved to the directory.
+    pub fn create<P: AsRef<Path>>(directory: P, seed: i64) -> Result<Self, SaveError> {
+        let mut world = Self::new(seed);
+        world.save = Some(WorldSave::create(directory, seed)?);
+        Ok(world)
+    }
+
+    /// Opens a world that was saved to the directory. Its chunks are loaded from the save as they
+    /// come into range.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Self, SaveError> {
+        let (save, seed, pending_blocks) = WorldSave::open(directory)?;
+        let mut world = Self::new(seed);
+        world.pending_blocks = pending_blocks;
+        world.save = Some(save);
+        Ok(world)
+    }
+
+    /// Returns the save the world is kept in, if it has one.
+    pub fn world_save(&self) -> Option<&WorldSave> {
+        self.save.as_ref()
+    }
+
+    /// Writes every loaded chunk, along with every chunk unloaded since the last save, to the
+    /// world's save directory. Does nothing for worlds without one.
+    pub fn save(&mut self) -> Result<(), SaveError> {
+        if let Some(save) = &mut self.save {
+            for (&coords, chunk) in &self.chunks {
+                save.store_chunk(coords, chunk)?;
+                self.pending_blocks.remove(&coords);
+            }
+            save.flush(self.generator.seed(), &self.pending_blocks)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.generator.seed()
+    }
+
+    pub fn render_distance(&self) -> i32 {
+        self.render_distance
+    }
+
+    /// Sets the render distance. Chunks are re-queued and unloaded around the current stream
+    /// center right away.
+    pub fn set_render_distance(&mut self, render_distance: i32) {
+        self.render_distance = render_distance.max(0);
+        if let Some(center) = self.stream_center {
+            self.recenter(center);
         }
     }
 
-    pub fn generate_chunk(&self, _x: i32, _y: i32, _z: i32) {
-        todo!()
+    /// Generates the chunk at the chunk coordinates and stores it in the world. If a chunk already
+    /// exists there, it is kept and nothing is generated. Chunks that were saved are loaded instead
+    /// of being generated again.
+    pub fn generate_chunk(&mut self, coords: ChunkCoords) -> &mut Chunk {
+        if !self.chunks.contains_key(&coords) {
+            let (mut chunk, overflow) = match self.load_saved_chunk(coords) {
+                Some(chunk) => (chunk, Vec::new()),
+                None => Chunk::generate(coords.0, coords.1, coords.2, &self.generator),
+            };
+
+            // finish off anything neighboring chunks grew into this one. A saved chunk already has
+            // whatever was placed before it was saved, so only blocks that came in after that are
+            // still pending.
+            if let Some(pending) = self.pending_blocks.get(&coords) {
+                for (&(i, j, k), &block_type) in pending {
+                    decoration::place_if_free(&mut chunk, i, j, k, block_type);
+                }
+            }
+
+            chunk.set_level_of_detail(self.level_of_detail_at(coords));
+            self.chunks.insert(coords, chunk);
+            light::light_new_chunk(&mut self.chunks, coords);
+            self.add_pending_blocks(overflow);
+
+            // faces on the borders of the chunks around this one may be covered now
+            self.request_neighbor_mesh_updates(coords);
+        }
+
+        self.chunks.get_mut(&coords).unwrap()
     }
 
-    fn render(&self) {
-        todo!()
-        // let eye_chunk_x = c.position().x / (blocks::BLOCK_WIDTH * blocks::CHUNK_SIZE);
-        // let eye_chunk_y = c.position().y / (blocks::BLOCK_WIDTH * blocks::CHUNK_SIZE);
-        // let eye_chunk_z = c.position().z / (blocks::BLOCK_WIDTH * blocks::CHUNK_SIZE);
+    /// Loads the chunk from the save, if the world has one and the chunk is in it. A chunk that
+    /// fails to load is reported and generated again instead.
+    fn load_saved_chunk(&mut self, coords: ChunkCoords) -> Option<Chunk> {
+        let save = self.save.as_mut()?;
+        match save.load_chunk(coords) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                eprintln!("couldn't load chunk {:?}: {}", coords, e);
+                None
+            }
+        }
+    }
+
+    /// Removes the chunk from the world, keeping a copy in the save if the world has one.
+    fn unload_chunk(&mut self, coords: ChunkCoords) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&coords)?;
+        self.request_neighbor_mesh_updates(coords);
+
+        if let Some(save) = &mut self.save {
+            match save.store_chunk(coords, &chunk) {
+                Ok(()) => {
+                    self.pending_blocks.remove(&coords);
+                }
+                Err(e) => eprintln!("couldn't save chunk {:?}: {}", coords, e),
+            }
+        }
+
+        Some(chunk)
+    }
+
+    /// Keeps blocks that belong to other chunks until those chunks are saved, placing them right
+    /// away in any that are loaded. Blocks that are already pending were placed when their tree
+    /// first grew, so they're skipped rather than growing back after being dug out.
+    fn add_pending_blocks(&mut self, blocks: Vec<PendingBlock>) {
+        for pending in blocks {
+            let (x, y, z) = pending.position;
+            let (coords, index) = Self::split_block_position(x, y, z);
+
+            let chunk_pending = self.pending_blocks.entry(coords).or_default();
+            if chunk_pending.contains_key(&index) {
+                continue;
+            }
+            chunk_pending.insert(index, pending.block_type);
 
-        // for x in (eye_chunk_x - self.render_distance)..(eye_chunk_x + self.render_distance) {
-        //     if x < 0 || x >= self.terrain.len() {
-        //         continue;
-        //     }
-        //     for y in (eye_chunk_y - self.render_distance)..(eye_chunk_y + self.render_distance) {
-        //         if y < 0 || y >= self.terrain[x].len() {
-        //             continue;
-        //         }
-        //         for z in (eye_chunk_z - self.render_distance)..(eye_chunk_z + self.render_distance) {
-        //             if z < 0 || z >= self.terrain[x][y].len() {
-        //                 continue;
-        //             }
-        //             self.terrain[x][y][z].render();
-        //         }
-        //     }
-        // }
+            if let Some(chunk) = self.chunks.get_mut(&coords) {
+                decoration::place_if_free(chunk, index.0, index.1, index.2, pending.block_type);
+            }
+        }
+    }
+
+    /// Returns the chunk at the chunk coordinates, if it is loaded.
+    pub fn get_chunk(&self, coords: ChunkCoords) -> Option<&Chunk> {
+        self.chunks.get(&coords)
+    }
+
+    /// Returns the chunk at the chunk coordinates mutably, if it is loaded.
+    pub fn get_chunk_mut(&mut self, coords: ChunkCoords) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&coords)
+    }
+
+    /// Returns true if a chunk is loaded at the chunk coordinates.
+    pub fn has_chunk(&self, coords: ChunkCoords) -> bool {
+        self.chunks.contains_key(&coords)
+    }
+
+    /// Inserts a chunk into the world and lights it, returning the chunk that was there before, if
+    /// any.
+    pub fn insert_chunk(&mut self, coords: ChunkCoords, mut chunk: Chunk) -> Option<Chunk> {
+        chunk.set_level_of_detail(self.level_of_detail_at(coords));
+        let old = self.chunks.insert(coords, chunk);
+        light::light_new_chunk(&mut self.chunks, coords);
+        old
+    }
+
+    /// Removes the chunk at the chunk coordinates from the world and returns it, if it was loaded.
+    /// The chunk is kept in the world's save, if it has one.
+    pub fn evict_chunk(&mut self, coords: ChunkCoords) -> Option<Chunk> {
+        self.unload_chunk(coords)
+    }
+
+    /// Returns the coordinates of every loaded chunk.
+    pub fn loaded_chunk_coords(&self) -> impl Iterator<Item = &ChunkCoords> {
+        self.chunks.keys()
+    }
+
+    /// Returns the number of loaded chunks.
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the block at the world block position, or None if the chunk containing it isn't
+    /// loaded.
+    pub fn get_block(&self, x: i64, y: i64, z: i64) -> Option<Block> {
+        let (coords, (i, j, k)) = Self::split_block_position(x, y, z);
+        self.chunks.get(&coords).map(|c| c.at(i, j, k))
+    }
+
+    /// Sets the block at the world block position. Returns false if the chunk containing the
+    /// position isn't loaded, in which case nothing is changed.
+    pub fn set_block(&mut self, x: i64, y: i64, z: i64, block: Block) -> bool {
+        let (coords, (i, j, k)) = Self::split_block_position(x, y, z);
+        if let Some(chunk) = self.chunks.get_mut(&coords) {
+            chunk.set(i, j, k, block);
+        } else {
+            return false;
+        }
+
+        // a block on the border may cover or uncover faces in the chunk next to it
+        let last = CHUNK_BLOCK_WIDTH - 1;
+        for &direction in Direction::ALL.iter() {
+            let (di, dj, dk) = direction.offset();
+            let on_border = (di == -1 && i == 0)
+                || (di == 1 && i == last)
+                || (dj == -1 && j == 0)
+                || (dj == 1 && j == last)
+                || (dk == -1 && k == 0)
+                || (dk == 1 && k == last);
+
+            if on_border {
+                let neighbor = (coords.0 + di, coords.1 + dj, coords.2 + dk);
+                if let Some(chunk) = self.chunks.get_mut(&neighbor) {
+                    chunk.request_mesh_update();
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Returns the loaded chunks next to the chunk at the chunk coordinates.
+    pub fn neighbors_of(&self, coords: ChunkCoords) -> ChunkNeighbors<'_> {
+        let mut neighbors = ChunkNeighbors::default();
+        for &direction in Direction::ALL.iter() {
+            let (di, dj, dk) = direction.offset();
+            let neighbor = (coords.0 + di, coords.1 + dj, coords.2 + dk);
+            neighbors.set(direction, self.chunks.get(&neighbor));
+        }
+
+        neighbors
+    }
+
+    /// Marks the meshes of the loaded chunks around the chunk at the chunk coordinates as out of
+    /// date.
+    fn request_neighbor_mesh_updates(&mut self, coords: ChunkCoords) {
+        for &direction in Direction::ALL.iter() {
+            let (di, dj, dk) = direction.offset();
+            let neighbor = (coords.0 + di, coords.1 + dj, coords.2 + dk);
+            if let Some(chunk) = self.chunks.get_mut(&neighbor) {
+                chunk.request_mesh_update();
+            }
+        }
+    }
+
+    /// Splits a world block position into the coordinates of the chunk that contains it and the
+    /// block's index within that chunk. Works for negative positions too; block -1 is the last
+    /// block of chunk -1, not the first block of chunk 0.
+    pub fn split_block_position(x: i64, y: i64, z: i64) -> (ChunkCoords, (usize, usize, usize)) {
+        let width = CHUNK_BLOCK_WIDTH as i64;
+        (
+            (x.div_euclid(width), y.div_euclid(width), z.div_euclid(width)),
+            (
+                x.rem_euclid(width) as usize,
+                y.rem_euclid(width) as usize,
+                z.rem_euclid(width) as usize,
+            ),
+        )
+    }
+
+    /// Returns the coordinates of the chunk that contains the world-space position.
+    pub fn chunk_coords_at(position: cgmath::Point3<f32>) -> ChunkCoords {
+        (
+            (position.x / CHUNK_SIZE).floor() as i64,
+            (position.y / CHUNK_SIZE).floor() as i64,
+            (position.z / CHUNK_SIZE).floor() as i64,
+        )
+    }
+
+    /// Centers chunk streaming on the world-space position, which is usually where the camera or
+    /// the chicken is. Nothing happens unless the position has moved into a different chunk.
+    pub fn stream_around(&mut self, position: cgmath::Point3<f32>) {
+        let center = Self::chunk_coords_at(position);
+        if self.stream_center != Some(center) {
+            self.recenter(center);
+        }
+    }
+
+    /// Returns the number of chunks waiting to be loaded.
+    pub fn queued_chunk_count(&self) -> usize {
+        self.load_queue.len()
+    }
+
+    /// Unloads every chunk outside of the render distance around `center` and queues every
+    /// missing chunk inside of it, nearest first.
+    fn recenter(&mut self, center: ChunkCoords) {
+        self.stream_center = Some(center);
+
+        let render_distance = self.render_distance as i64;
+        let max_distance_squared = render_distance * render_distance;
+
+        let out_of_range: Vec<_> = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&coords| chunk_distance_squared(center, coords) > max_distance_squared)
+            .collect();
+        for coords in out_of_range {
+            self.unload_chunk(coords);
+        }
+
+        if let Some(save) = &mut self.save {
+            let loaded = self.chunks.keys().copied();
+            let seed = self.generator.seed();
+            if let Err(e) = save.evict_regions(loaded, seed, &self.pending_blocks) {
+                eprintln!("couldn't save regions that went out of range: {}", e);
+            }
+        }
+
+        let mut wanted = Vec::new();
+        for i in -render_distance..=render_distance {
+            for j in -render_distance..=render_distance {
+                for k in -render_distance..=render_distance {
+                    let coords = (center.0 + i, center.1 + j, center.2 + k);
+                    if i * i + j * j + k * k <= max_distance_squared
+                        && !self.chunks.contains_key(&coords)
+                    {
+                        wanted.push(coords);
+                    }
+                }
+            }
+        }
+
+        wanted.sort_by_key(|&coords| chunk_distance_squared(center, coords));
+        self.load_queue = wanted.into();
+
+        self.update_levels_of_detail();
+    }
+
+    /// Returns how coarsely the chunk at the chunk coordinates should be meshed, going by its
+    /// distance from the stream center.
+    fn level_of_detail_at(&self, coords: ChunkCoords) -> LevelOfDetail {
+        match self.stream_center {
+            Some(center) => {
+                LevelOfDetail::for_distance_squared(chunk_distance_squared(center, coords))
+            }
+            None => LevelOfDetail::Full,
+        }
+    }
+
+    /// Changes the level of detail of every loaded chunk that moved in or out of range of one.
+    /// The chunks around those are remeshed too, since they close off their borders with chunks
+    /// at other levels of detail.
+    fn update_levels_of_detail(&mut self) {
+        let loaded: Vec<_> = self.chunks.keys().copied().collect();
+        for coords in loaded {
+            let detail = self.level_of_detail_at(coords);
+            let chunk = self.chunks.get_mut(&coords).unwrap();
+            if chunk.set_level_of_detail(detail) {
+                self.request_neighbor_mesh_updates(coords);
+            }
+        }
+    }
+
+    /// Loads queued chunks until the per-frame budget runs out. Each chunk is timed as a whole,
+    /// lighting included, and one isn't started if it would likely finish past the budget.
+    fn load_queued_chunks(&mut self) {
+        let start = Instant::now();
+        let mut loaded_any = false;
+        while let Some(coords) = self.load_queue.pop_front() {
+            if loaded_any && start.elapsed() + self.chunk_load_time > CHUNK_LOAD_BUDGET {
+                self.load_queue.push_front(coords);
+                break;
+            }
+
+            let chunk_start = Instant::now();
+            self.generate_chunk(coords);
+            self.chunk_load_time = (self.chunk_load_time * 3 + chunk_start.elapsed()) / 4;
+            loaded_any = true;
+        }
+    }
+
+    /// Loads queued chunks, updates light around blocks that changed, uploads the meshes that the
+    /// mesh workers have finished and hands every chunk with an out of date mesh to the workers.
+    /// The bind group layout is the one made by `render::make_chunk_bind_group_layout`.
+    pub fn logic(
+        &mut self,
+        device: &wgpu::Device,
+        chunk_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.load_queued_chunks();
+        light::update(&mut self.chunks);
+
+        for result in self.mesh_workers.finished() {
+            if self.meshing.get(&result.coords) == Some(&result.version) {
+                self.meshing.remove(&result.coords);
+            }
+
+            // meshes of chunks that changed or were unloaded after the job was made are stale
+            if let Some(chunk) = self.chunks.get_mut(&result.coords) {
+                if chunk.version() == result.version {
+                    chunk.set_mesh(device, chunk_bind_group_layout, &result);
+                }
+            }
+        }
+
+        let mut outdated: Vec<ChunkCoords> = self
+            .chunks
+            .iter()
+            .filter(|(coords, chunk)| {
+                chunk.needs_mesh_update() && self.meshing.get(coords) != Some(&chunk.version())
+            })
+            .map(|(&coords, _)| coords)
+            .collect();
+
+        // nearest chunks first, so what's around the camera shows up first
+        if let Some(center) = self.stream_center {
+            outdated.sort_by_key(|&coords| chunk_distance_squared(center, coords));
+        }
+
+        for coords in outdated {
+            let chunk = &self.chunks[&coords];
+            self.meshing.insert(coords, chunk.version());
+            self.mesh_workers
+                .submit(chunk.mesh_job(coords, &self.neighbors_of(coords)));
+        }
+    }
+
+    /// Renders every loaded chunk that the camera can see. This method assumes that the block
+    /// texture bind group and the quad index buffer have already been bound to the render pass.
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, frustum: &Frustum) {
+        for chunk in self.chunks.values() {
+            if frustum.intersects(&chunk.bounding_box()) {
+                chunk.render(render_pass);
+            }
+        }
+    }
+
+    /// Renders the translucent blocks of every visible chunk, farthest chunk first, so that nearer
+    /// glass and water blend over what's behind them. Must be called after `render`.
+    pub fn render_translucent<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        eye: cgmath::Point3<f32>,
+        frustum: &Frustum,
+    ) {
+        let distance_squared = |chunk: &Chunk| {
+            let center = chunk.center();
+            let (dx, dy, dz) = (center[0] - eye.x, center[1] - eye.y, center[2] - eye.z);
+            dx * dx + dy * dy + dz * dz
+        };
+
+        let mut chunks: Vec<_> = self
+            .chunks
+            .values()
+            .filter(|chunk| chunk.has_translucent_mesh())
+            .filter(|chunk| frustum.intersects(&chunk.bounding_box()))
+            .map(|chunk| (distance_squared(chunk), chunk))
+            .collect();
+        chunks.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (_, chunk) in chunks {
+            chunk.render_translucent(render_pass);
+        }
     }
 }
 
+/// Returns the squared distance, in chunks, between two chunk coordinates.
+fn chunk_distance_squared(a: ChunkCoords, b: ChunkCoords) -> i64 {
+    let (di, dj, dk) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+    di * di + dj * dj + dk * dk
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Axis {
     X,
@@ -63,7 +544,7 @@ pub enum Axis {
     Z,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     North,
     South,
@@ -72,3 +553,162 @@ pub enum Direction {
     Up,
     Down,
 }
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    /// Returns the step, in blocks or chunks, that moves one over in this direction. North is +z
+    /// and east is -x, matching the faces that the chunk mesher draws.
+    pub fn offset(self) -> (i64, i64, i64) {
+        match self {
+            Direction::North => (0, 0, 1),
+            Direction::South => (0, 0, -1),
+            Direction::East => (-1, 0, 0),
+            Direction::West => (1, 0, 0),
+            Direction::Up => (0, 1, 0),
+            Direction::Down => (0, -1, 0),
+        }
+    }
+
+    /// Returns the axis that this direction points along.
+    pub fn axis(self) -> Axis {
+        match self {
+            Direction::East | Direction::West => Axis::X,
+            Direction::Up | Direction::Down => Axis::Y,
+            Direction::North | Direction::South => Axis::Z,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: i64 = 1234;
+
+    /// A chunk that trees rooted in the chunks around it grow into, for `SEED`.
+    const OVERGROWN: ChunkCoords = (-2, 0, -2);
+
+    fn chunks_around(coords: ChunkCoords) -> Vec<ChunkCoords> {
+        let mut around = Vec::new();
+        for di in -1..=1 {
+            for dk in -1..=1 {
+                if (di, dk) != (0, 0) {
+                    around.push((coords.0 + di, coords.1, coords.2 + dk));
+                }
+            }
+        }
+        around
+    }
+
+    /// Returns the array position of a block in the chunk at `OVERGROWN` that a tree from another
+    /// chunk grew there.
+    fn grown_in_block(chunk: &Chunk) -> (usize, usize, usize) {
+        let (alone, _) = Chunk::generate(
+            OVERGROWN.0,
+            OVERGROWN.1,
+            OVERGROWN.2,
+            &TerrainGenerator::new(SEED),
+        );
+        let width = CHUNK_BLOCK_WIDTH;
+        (0..width * width * width)
+            .map(|n| (n / (width * width), n / width % width, n % width))
+            .find(|&(i, j, k)| chunk.block_type_at(i, j, k) != alone.block_type_at(i, j, k))
+            .expect("nothing grew into the chunk")
+    }
+
+    #[test]
+    fn neighbor_order_doesnt_change_a_chunk() {
+        let mut first_world = World::new(SEED);
+        first_world.generate_chunk(OVERGROWN);
+        for &coords in &chunks_around(OVERGROWN) {
+            first_world.generate_chunk(coords);
+        }
+
+        let mut last_world = World::new(SEED);
+        for &coords in chunks_around(OVERGROWN).iter().rev() {
+            last_world.generate_chunk(coords);
+        }
+        last_world.generate_chunk(OVERGROWN);
+
+        let hash = |world: &World| world.get_chunk(OVERGROWN).unwrap().content_hash();
+        assert_eq!(hash(&first_world), hash(&last_world));
+
+        // the neighbors have to have grown something into it for the test to mean anything
+        let (alone, _) = Chunk::generate(
+            OVERGROWN.0,
+            OVERGROWN.1,
+            OVERGROWN.2,
+            &TerrainGenerator::new(SEED),
+        );
+        assert_ne!(hash(&first_world), alone.content_hash());
+    }
+
+    #[test]
+    fn dug_out_trees_dont_grow_back() {
+        let directory = std::env::temp_dir().join(format!("chicky-world-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let mut world = World::create(&directory, SEED).unwrap();
+        for &coords in &chunks_around(OVERGROWN) {
+            world.generate_chunk(coords);
+        }
+        world.generate_chunk(OVERGROWN);
+
+        let chunk = world.get_chunk_mut(OVERGROWN).unwrap();
+        let (i, j, k) = grown_in_block(chunk);
+        chunk.set(i, j, k, Block::from(BlockType::Air));
+        world.save().unwrap();
+        drop(world);
+
+        let mut reopened = World::open(&directory).unwrap();
+        for &coords in &chunks_around(OVERGROWN) {
+            reopened.generate_chunk(coords);
+        }
+        let block_type = reopened.generate_chunk(OVERGROWN).block_type_at(i, j, k);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(block_type, BlockType::Air);
+    }
+
+    #[test]
+    fn regenerated_chunks_get_their_trees_back() {
+        let mut world = World::new(SEED);
+        for &coords in &chunks_around(OVERGROWN) {
+            world.generate_chunk(coords);
+        }
+        let grown = world.generate_chunk(OVERGROWN).content_hash();
+
+        // without a save, the chunk is generated from scratch while its neighbors stay loaded
+        world.evict_chunk(OVERGROWN);
+        let regenerated = world.generate_chunk(OVERGROWN).content_hash();
+
+        assert_eq!(regenerated, grown);
+    }
+
+    #[test]
+    fn regenerated_roots_dont_regrow_dug_out_blocks() {
+        let mut world = World::new(SEED);
+        for &coords in &chunks_around(OVERGROWN) {
+            world.generate_chunk(coords);
+        }
+        let chunk = world.generate_chunk(OVERGROWN);
+        let (i, j, k) = grown_in_block(chunk);
+        chunk.set(i, j, k, Block::from(BlockType::Air));
+
+        for &coords in &chunks_around(OVERGROWN) {
+            world.evict_chunk(coords);
+            world.generate_chunk(coords);
+        }
+
+        let block_type = world.get_chunk(OVERGROWN).unwrap().block_type_at(i, j, k);
+        assert_eq!(block_type, BlockType::Air);
+    }
+}
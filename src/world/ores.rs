@@ -0,0 +1,358 @@
+use super::terrain::{mix_seed, TerrainColumn, TerrainGenerator};
+use super::ChunkCoords;
+use crate::blocks::{Block, BlockType, Chunk, CHUNK_BLOCK_WIDTH};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// How an ore is scattered through the ground.
+#[derive(Debug, Copy, Clone)]
+pub enum OrePlacement {
+    /// Clumps of ore that wander through stone. `veins_per_chunk` may be fractional; the leftover
+    /// is the chance of one more vein.
+    Veins { veins_per_chunk: f32, vein_size: u32 },
+
+    /// Single blocks of ore that only replace stone on the walls of caves, next to air. Each
+    /// candidate block becomes ore with probability `chance`.
+    CaveWall { chance: f32 },
+}
+
+/// Where and how often an ore is generated.
+#[derive(Debug, Copy, Clone)]
+pub struct OreDistribution {
+    pub block_type: BlockType,
+    pub placement: OrePlacement,
+
+    /// Lowest world block height the ore can generate at.
+    pub min_height: i64,
+
+    /// Highest world block height the ore can generate at.
+    pub max_height: i64,
+}
+
+/// Ore distributions, from most common to most rare as ranked in ores.md. Rarer ores have fewer
+/// and smaller veins and only show up deeper down.
+///
+/// Where this differs from ores.md:
+/// - Iron and copper aren't in ores.md, so they're ranked by their abundance in materials.md.
+/// - Pearls, amber, titanium and platinum are left out because they don't have block types or
+///   textures yet. Pearls will also need a placement that only puts them in oceans.
+/// - Diamond isn't ranked in ores.md, so diamond ore isn't generated even though the block
+///   type exists.
+pub const ORE_DISTRIBUTIONS: [OreDistribution; 5] = [
+    OreDistribution {
+        block_type: BlockType::CoalOre,
+        placement: OrePlacement::Veins {
+            veins_per_chunk: 20.0,
+            vein_size: 12,
+        },
+        min_height: -256,
+        max_height: 64,
+    },
+    OreDistribution {
+        block_type: BlockType::IronOre,
+        placement: OrePlacement::Veins {
+            veins_per_chunk: 12.0,
+            vein_size: 8,
+        },
+        min_height: -256,
+        max_height: 16,
+    },
+    OreDistribution {
+        block_type: BlockType::CopperOre,
+        placement: OrePlacement::Veins {
+            veins_per_chunk: 10.0,
+            vein_size: 8,
+        },
+        min_height: -192,
+        max_height: 24,
+    },
+    OreDistribution {
+        block_type: BlockType::Crystal,
+        placement: OrePlacement::CaveWall { chance: 0.02 },
+        min_height: -512,
+        max_height: -8,
+    },
+    OreDistribution {
+        block_type: BlockType::GoldOre,
+        placement: OrePlacement::Veins {
+            veins_per_chunk: 2.0,
+            vein_size: 6,
+        },
+        min_height: -512,
+        max_height: -48,
+    },
+];
+
+/// Scatters ores through a freshly generated chunk. This should run after everything that carves
+/// air out of the terrain, so that cave wall ores can find their walls.
+pub fn place_ores(
+    chunk: &mut Chunk,
+    generator: &TerrainGenerator,
+    coords: ChunkCoords,
+    columns: &[[TerrainColumn; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH],
+) {
+    let seed = generator.seed();
+    let origin_y = coords.1 * CHUNK_BLOCK_WIDTH as i64;
+
+    for (index, ore) in ORE_DISTRIBUTIONS.iter().enumerate() {
+        // skip chunks entirely outside of the ore's height band
+        if origin_y > ore.max_height || origin_y + (CHUNK_BLOCK_WIDTH as i64) <= ore.min_height {
+            continue;
+        }
+
+        let mut rng = StdRng::seed_from_u64(mix_seed(seed, coords, 0x6f72_6500 + index as u64));
+        let in_band = |j: usize| {
+            let y = origin_y + j as i64;
+            y >= ore.min_height && y <= ore.max_height
+        };
+
+        match ore.placement {
+            OrePlacement::Veins {
+                veins_per_chunk,
+                vein_size,
+            } => {
+                let mut vein_count = veins_per_chunk.floor() as u32;
+                if rng.gen::<f32>() < veins_per_chunk.fract() {
+                    vein_count += 1;
+                }
+
+                for _ in 0..vein_count {
+                    let mut pos = [
+                        rng.gen_range(0, CHUNK_BLOCK_WIDTH as i64),
+                        rng.gen_range(0, CHUNK_BLOCK_WIDTH as i64),
+                        rng.gen_range(0, CHUNK_BLOCK_WIDTH as i64),
+                    ];
+
+                    for _ in 0..vein_size {
+                        let (i, j, k) = (pos[0] as usize, pos[1] as usize, pos[2] as usize);
+                        if in_band(j) && chunk.block_type_at(i, j, k) == BlockType::Stone {
+                            chunk.set(i, j, k, Block::from(ore.block_type));
+                        }
+
+                        // wander one block along a random axis, staying inside the chunk
+                        let axis = rng.gen_range(0, 3);
+                        let step = if rng.gen::<bool>() { 1 } else { -1 };
+                        pos[axis] = (pos[axis] + step).max(0).min(CHUNK_BLOCK_WIDTH as i64 - 1);
+                    }
+                }
+            }
+            OrePlacement::CaveWall { chance } => {
+                let mut walls = CaveWalls::new(generator, coords, columns);
+                for (i, row) in columns.iter().enumerate() {
+                    for (k, column) in row.iter().enumerate() {
+                        let surface_height = column.surface_height;
+                        for j in 0..CHUNK_BLOCK_WIDTH {
+                            if in_band(j)
+                                && origin_y + (j as i64) < surface_height
+                                && chunk.block_type_at(i, j, k) == BlockType::Stone
+                                && walls.touches_air(chunk, i, j, k)
+                                && rng.gen::<f32>() < chance
+                            {
+                                chunk.set(i, j, k, Block::from(ore.block_type));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finds the walls of caves for cave wall ores, including walls whose open side is in a
+/// neighboring chunk.
+struct CaveWalls<'a> {
+    generator: &'a TerrainGenerator,
+    columns: &'a [[TerrainColumn; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH],
+
+    /// World block position of the chunk's first block.
+    origin: (i64, i64, i64),
+
+    /// Columns just outside of the chunk, by world block position, worked out as they're needed.
+    outside_columns: HashMap<(i64, i64), TerrainColumn>,
+}
+
+impl<'a> CaveWalls<'a> {
+    fn new(
+        generator: &'a TerrainGenerator,
+        coords: ChunkCoords,
+        columns: &'a [[TerrainColumn; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH],
+    ) -> Self {
+        let width = CHUNK_BLOCK_WIDTH as i64;
+        Self {
+            generator,
+            columns,
+            origin: (coords.0 * width, coords.1 * width, coords.2 * width),
+            outside_columns: HashMap::new(),
+        }
+    }
+
+    /// Returns true if any block sharing a face with the block at the array position is air.
+    /// Neighbors in other chunks may not have been generated yet, so whether they're air comes
+    /// from the terrain and caves at their position, which don't depend on generation order.
+    fn touches_air(&mut self, chunk: &Chunk, i: usize, j: usize, k: usize) -> bool {
+        const NEIGHBORS: [(i64, i64, i64); 6] = [
+            (1, 0, 0),
+            (-1, 0, 0),
+            (0, 1, 0),
+            (0, -1, 0),
+            (0, 0, 1),
+            (0, 0, -1),
+        ];
+
+        NEIGHBORS.iter().any(|(di, dj, dk)| {
+            let (ni, nj, nk) = (i as i64 + di, j as i64 + dj, k as i64 + dk);
+            let range = 0..CHUNK_BLOCK_WIDTH as i64;
+            if range.contains(&ni) && range.contains(&nj) && range.contains(&nk) {
+                return chunk.block_type_at(ni as usize, nj as usize, nk as usize)
+                    == BlockType::Air;
+            }
+
+            let (x, y, z) = (self.origin.0 + ni, self.origin.1 + nj, self.origin.2 + nk);
+            let column = if range.contains(&ni) && range.contains(&nk) {
+                self.columns[ni as usize][nk as usize]
+            } else {
+                let generator = self.generator;
+                *self
+                    .outside_columns
+                    .entry((x, z))
+                    .or_insert_with(|| generator.column_at(x, z))
+            };
+            self.generator.carved_block_type_at(x, y, z, &column) == BlockType::Air
+        })
+    }
+}
+
+/// Ore counts gathered from a generated region of the world. Used to tune `ORE_DISTRIBUTIONS`.
+#[derive(Debug, Default)]
+pub struct OreStatistics {
+    chunk_count: usize,
+    stone_count: usize,
+
+    /// Number of blocks and the lowest and highest world height found, per ore.
+    ores: HashMap<BlockType, (usize, i64, i64)>,
+}
+
+impl OreStatistics {
+    /// Generates every chunk between `from` and `to` (inclusive) and counts the ores in them.
+    pub fn gather(generator: &TerrainGenerator, from: ChunkCoords, to: ChunkCoords) -> Self {
+        let mut stats = Self::default();
+
+        for chunk_i in from.0..=to.0 {
+            for chunk_j in from.1..=to.1 {
+                for chunk_k in from.2..=to.2 {
+                    let (chunk, _) = Chunk::generate(chunk_i, chunk_j, chunk_k, generator);
+                    stats.count_chunk(&chunk, chunk_j);
+                }
+            }
+        }
+
+        stats
+    }
+
+    fn count_chunk(&mut self, chunk: &Chunk, chunk_j: i64) {
+        self.chunk_count += 1;
+
+        for i in 0..CHUNK_BLOCK_WIDTH {
+            for j in 0..CHUNK_BLOCK_WIDTH {
+                for k in 0..CHUNK_BLOCK_WIDTH {
+                    let ty = chunk.block_type_at(i, j, k);
+                    if ty == BlockType::Stone {
+                        self.stone_count += 1;
+                    } else if ORE_DISTRIBUTIONS.iter().any(|o| o.block_type == ty) {
+                        let y = chunk_j * CHUNK_BLOCK_WIDTH as i64 + j as i64;
+                        let entry = self.ores.entry(ty).or_insert((0, y, y));
+                        entry.0 += 1;
+                        entry.1 = entry.1.min(y);
+                        entry.2 = entry.2.max(y);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Display for OreStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} chunks, {} stone blocks",
+            self.chunk_count, self.stone_count
+        )?;
+        writeln!(
+            f,
+            "{:<12} {:>10} {:>12} {:>14} {:>8} {:>8}",
+            "ore", "blocks", "per chunk", "per 1k stone", "lowest", "highest"
+        )?;
+
+        for ore in ORE_DISTRIBUTIONS.iter() {
+            let (count, lowest, highest) = self
+                .ores
+                .get(&ore.block_type)
+                .copied()
+                .unwrap_or((0, 0, 0));
+            let per_chunk = count as f32 / self.chunk_count.max(1) as f32;
+            let per_thousand_stone = count as f32 * 1000.0 / self.stone_count.max(1) as f32;
+
+            writeln!(
+                f,
+                "{:<12} {:>10} {:>12.2} {:>14.2} {:>8} {:>8}",
+                format!("{:?}", ore.block_type),
+                count,
+                per_chunk,
+                per_thousand_stone,
+                lowest,
+                highest
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walls_open_to_the_next_chunk_can_get_crystals() {
+        let generator = TerrainGenerator::new(1234);
+        let last = CHUNK_BLOCK_WIDTH - 1;
+
+        let mut found = 0;
+        for chunk_i in 0..4 {
+            let coords = (chunk_i, -2, 1);
+            let columns = generator.columns_for_chunk(coords.0, coords.2);
+            let (chunk, _) = Chunk::generate(coords.0, coords.1, coords.2, &generator);
+            let (next, _) = Chunk::generate(coords.0 + 1, coords.1, coords.2, &generator);
+            let mut walls = CaveWalls::new(&generator, coords, &columns);
+
+            // stone on the chunk's +x face whose only open side is in the next chunk over
+            for j in 1..last {
+                for k in 1..last {
+                    let inside = [
+                        (last - 1, j, k),
+                        (last, j - 1, k),
+                        (last, j + 1, k),
+                        (last, j, k - 1),
+                        (last, j, k + 1),
+                    ];
+                    let open_inside = inside
+                        .iter()
+                        .any(|&(ni, nj, nk)| chunk.block_type_at(ni, nj, nk) == BlockType::Air);
+
+                    if chunk.block_type_at(last, j, k) == BlockType::Stone
+                        && !open_inside
+                        && next.block_type_at(0, j, k) == BlockType::Air
+                    {
+                        assert!(walls.touches_air(&chunk, last, j, k));
+                        found += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(found > 0, "no cave walls across chunk borders to test");
+    }
+}
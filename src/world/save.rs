@@ -0,0 +1,789 @@
+//! The on-disk world format. A world save is a directory holding a `world.dat` header and a
+//! `regions` directory of region files, each of which stores up to
+//! `REGION_WIDTH`×`REGION_WIDTH`×`REGION_WIDTH` chunks. All numbers are little-endian.
+//!
+//! `world.dat`:
+//!
+//! | field          | type                                                   |
+//! |----------------|--------------------------------------------------------|
+//! | magic          | `b"CCWD"`                                              |
+//! | version        | u16                                                    |
+//! | seed           | i64                                                    |
+//! | pending count  | u32                                                    |
+//! | pending blocks | chunk coords (3×i64), block index (3×u8), block id u8  |
+//!
+//! `regions/r.X.Y.Z.ccr`:
+//!
+//! | field       | type                                                |
+//! |-------------|-----------------------------------------------------|
+//! | magic       | `b"CCRG"`                                           |
+//! | version     | u16                                                 |
+//! | chunk count | u16                                                 |
+//! | chunks      | index in region u16, payload length u32, payload    |
+//!
+//! `player.dat` holds `b"CCPL"` and the version, followed by the player's chicken as written by
+//! its `Persist` implementation.
+//!
+//! A chunk payload is a palette of the distinct blocks in the chunk followed by a run-length
+//! encoding of the chunk's blocks, in `blocks[i][j][k]` order, as indices into the palette:
+//!
+//! | field         | type                                                             |
+//! |---------------|------------------------------------------------------------------|
+//! | palette size  | u16                                                              |
+//! | palette       | block id u8, health f32, has offset u8, offset (3×f32) if it has |
+//! | run count     | u32                                                              |
+//! | runs          | palette index u16, run length u16                                |
+
+use super::ChunkCoords;
+use crate::blocks::{Block, BlockType, Chunk, CHUNK_BLOCK_WIDTH};
+use crate::characters::Chicken;
+use crate::traits::Persist;
+use cgmath::Vector3;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Version of the save format. Bump it whenever the layout of any file changes.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Width, in chunks, of the cube of chunks stored in one region file.
+pub const REGION_WIDTH: i64 = 8;
+
+const WORLD_MAGIC: &[u8; 4] = b"CCWD";
+const REGION_MAGIC: &[u8; 4] = b"CCRG";
+const PLAYER_MAGIC: &[u8; 4] = b"CCPL";
+const WORLD_FILE_NAME: &str = "world.dat";
+const PLAYER_FILE_NAME: &str = "player.dat";
+const REGION_DIRECTORY_NAME: &str = "regions";
+
+/// Coordinates of a region in the region grid.
+type RegionCoords = (i64, i64, i64);
+
+/// Blocks that decoration placed across chunk borders, keyed like `World::pending_blocks`.
+pub type PendingBlocks = HashMap<ChunkCoords, HashMap<(usize, usize, usize), BlockType>>;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+
+    /// The file doesn't start with the magic bytes it should, so it probably isn't a save file.
+    BadMagic(PathBuf),
+
+    /// The file was written by a different version of the save format.
+    UnsupportedVersion(PathBuf, u16),
+
+    /// The file is truncated or holds values that make no sense.
+    Corrupt(String),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "save I/O error: {}", e),
+            SaveError::BadMagic(path) => write!(f, "`{}` is not a save file", path.display()),
+            SaveError::UnsupportedVersion(path, version) => write!(
+                f,
+                "`{}` has save format version {}, but only version {} is supported",
+                path.display(),
+                version,
+                FORMAT_VERSION
+            ),
+            SaveError::Corrupt(detail) => write!(f, "corrupt save: {}", detail),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+/// The chunks of one region file, still encoded.
+#[derive(Default)]
+struct Region {
+    chunks: HashMap<u16, Vec<u8>>,
+
+    /// True if chunks were stored since the region was last written to disk.
+    dirty: bool,
+}
+
+/// A world save directory. Region files are read the first time one of their chunks is needed and
+/// kept in memory, encoded, until `evict_regions` finds that none of their chunks are loaded any
+/// more; changes are written by `flush`, which eviction also does.
+pub struct WorldSave {
+    directory: PathBuf,
+    regions: HashMap<RegionCoords, Region>,
+}
+
+impl WorldSave {
+    /// Makes a new, empty save in the directory and writes its header. Anything already saved in
+    /// the directory is replaced as chunks are stored.
+    pub fn create<P: AsRef<Path>>(directory: P, seed: i64) -> Result<Self, SaveError> {
+        let save = Self {
+            directory: directory.as_ref().to_path_buf(),
+            regions: HashMap::new(),
+        };
+
+        fs::create_dir_all(save.directory.join(REGION_DIRECTORY_NAME))?;
+        save.write_header(seed, &HashMap::new())?;
+        Ok(save)
+    }
+
+    /// Opens an existing save, returning it along with the world seed and pending blocks from its
+    /// header.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<(Self, i64, PendingBlocks), SaveError> {
+        let save = Self {
+            directory: directory.as_ref().to_path_buf(),
+            regions: HashMap::new(),
+        };
+
+        let path = save.directory.join(WORLD_FILE_NAME);
+        let bytes = fs::read(&path)?;
+        let mut reader = Reader::new(&bytes);
+        reader.expect_header(WORLD_MAGIC, &path)?;
+
+        let seed = reader.read_i64()?;
+        let pending_count = reader.read_u32()?;
+        let mut pending_blocks = PendingBlocks::new();
+        for _ in 0..pending_count {
+            let coords = (reader.read_i64()?, reader.read_i64()?, reader.read_i64()?);
+            let index = (
+                reader.read_index()?,
+                reader.read_index()?,
+                reader.read_index()?,
+            );
+            let block_type = reader.read_block_type()?;
+
+            pending_blocks
+                .entry(coords)
+                .or_default()
+                .insert(index, block_type);
+        }
+
+        Ok((save, seed, pending_blocks))
+    }
+
+    /// Returns true if the directory holds a world save.
+    pub fn exists<P: AsRef<Path>>(directory: P) -> bool {
+        directory.as_ref().join(WORLD_FILE_NAME).is_file()
+    }
+
+    /// Loads the player's chicken, or returns None if it was never saved.
+    pub fn load_player(&self) -> Result<Option<Chicken>, SaveError> {
+        let path = self.directory.join(PLAYER_FILE_NAME);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut reader = Reader::new(&bytes);
+        reader.expect_header(PLAYER_MAGIC, &path)?;
+        Chicken::read_from(&mut reader).map(Some)
+    }
+
+    /// Writes the player's chicken to disk right away.
+    pub fn store_player(&self, chicken: &Chicken) -> Result<(), SaveError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(PLAYER_MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        chicken.write_to(&mut bytes);
+
+        write_replacing(&self.directory.join(PLAYER_FILE_NAME), &bytes)
+    }
+
+    /// Loads the chunk at the chunk coordinates, or returns None if it was never saved.
+    pub fn load_chunk(&mut self, coords: ChunkCoords) -> Result<Option<Chunk>, SaveError> {
+        let (region_coords, index) = split_chunk_coords(coords);
+        let region = self.region(region_coords)?;
+
+        match region.chunks.get(&index) {
+            Some(payload) => decode_chunk(coords, payload).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the chunk at the chunk coordinates, replacing any earlier copy. It isn't written to
+    /// disk until the next `flush`.
+    pub fn store_chunk(&mut self, coords: ChunkCoords, chunk: &Chunk) -> Result<(), SaveError> {
+        let (region_coords, index) = split_chunk_coords(coords);
+        let region = self.region(region_coords)?;
+
+        region.chunks.insert(index, encode_chunk(chunk));
+        region.dirty = true;
+        Ok(())
+    }
+
+    /// Writes the header and every region with stored chunks to disk.
+    pub fn flush(&mut self, seed: i64, pending_blocks: &PendingBlocks) -> Result<(), SaveError> {
+        self.write_header(seed, pending_blocks)?;
+
+        for (&region_coords, region) in self.regions.iter_mut() {
+            if !region.dirty {
+                continue;
+            }
+
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(REGION_MAGIC);
+            bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+            bytes.extend_from_slice(&(region.chunks.len() as u16).to_le_bytes());
+
+            // sorted so that saving the same chunks always writes the same file
+            let mut indices: Vec<_> = region.chunks.keys().copied().collect();
+            indices.sort_unstable();
+            for index in indices {
+                let payload = &region.chunks[&index];
+                bytes.extend_from_slice(&index.to_le_bytes());
+                bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(payload);
+            }
+
+            let path = region_path(&self.directory, region_coords);
+            write_replacing(&path, &bytes)?;
+            region.dirty = false;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets every region that none of the loaded chunks are in, so the save doesn't keep the
+    /// whole explored world in memory. If any region is forgotten, everything is flushed first, so
+    /// that the regions on disk and the pending blocks in the header always go together and a
+    /// crash only loses what changed since the last region went out of use.
+    pub fn evict_regions<I>(
+        &mut self,
+        loaded: I,
+        seed: i64,
+        pending_blocks: &PendingBlocks,
+    ) -> Result<(), SaveError>
+    where
+        I: IntoIterator<Item = ChunkCoords>,
+    {
+        let in_use: HashSet<RegionCoords> = loaded
+            .into_iter()
+            .map(|coords| split_chunk_coords(coords).0)
+            .collect();
+        if self.regions.keys().all(|region| in_use.contains(region)) {
+            return Ok(());
+        }
+
+        self.flush(seed, pending_blocks)?;
+        self.regions.retain(|region, _| in_use.contains(region));
+        Ok(())
+    }
+
+    fn write_header(&self, seed: i64, pending_blocks: &PendingBlocks) -> Result<(), SaveError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(WORLD_MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&seed.to_le_bytes());
+
+        let pending_count: usize = pending_blocks.values().map(HashMap::len).sum();
+        bytes.extend_from_slice(&(pending_count as u32).to_le_bytes());
+        for (coords, blocks) in pending_blocks {
+            for (index, block_type) in blocks {
+                bytes.extend_from_slice(&coords.0.to_le_bytes());
+                bytes.extend_from_slice(&coords.1.to_le_bytes());
+                bytes.extend_from_slice(&coords.2.to_le_bytes());
+                bytes.extend_from_slice(&[
+                    index.0 as u8,
+                    index.1 as u8,
+                    index.2 as u8,
+                    block_type.id(),
+                ]);
+            }
+        }
+
+        write_replacing(&self.directory.join(WORLD_FILE_NAME), &bytes)
+    }
+
+    /// Returns the region at the region coordinates, reading it from disk if it isn't cached yet.
+    fn region(&mut self, region_coords: RegionCoords) -> Result<&mut Region, SaveError> {
+        if !self.regions.contains_key(&region_coords) {
+            let region = self.read_region(region_coords)?;
+            self.regions.insert(region_coords, region);
+        }
+
+        Ok(self.regions.get_mut(&region_coords).unwrap())
+    }
+
+    fn read_region(&self, region_coords: RegionCoords) -> Result<Region, SaveError> {
+        let path = region_path(&self.directory, region_coords);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Region::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut reader = Reader::new(&bytes);
+        reader.expect_header(REGION_MAGIC, &path)?;
+
+        let mut region = Region::default();
+        let chunk_count = reader.read_u16()?;
+        for _ in 0..chunk_count {
+            let index = reader.read_u16()?;
+            let length = reader.read_u32()? as usize;
+            let payload = reader.read_bytes(length)?;
+            region.chunks.insert(index, payload.to_vec());
+        }
+
+        Ok(region)
+    }
+}
+
+fn region_path(directory: &Path, region_coords: RegionCoords) -> PathBuf {
+    directory.join(REGION_DIRECTORY_NAME).join(format!(
+        "r.{}.{}.{}.ccr",
+        region_coords.0, region_coords.1, region_coords.2
+    ))
+}
+
+/// Splits chunk coordinates into the coordinates of the region that holds the chunk and the
+/// chunk's index within that region.
+fn split_chunk_coords(coords: ChunkCoords) -> (RegionCoords, u16) {
+    let region_coords = (
+        coords.0.div_euclid(REGION_WIDTH),
+        coords.1.div_euclid(REGION_WIDTH),
+        coords.2.div_euclid(REGION_WIDTH),
+    );
+    let index = coords.0.rem_euclid(REGION_WIDTH) * REGION_WIDTH * REGION_WIDTH
+        + coords.1.rem_euclid(REGION_WIDTH) * REGION_WIDTH
+        + coords.2.rem_euclid(REGION_WIDTH);
+
+    (region_coords, index as u16)
+}
+
+/// Writes the file through a temporary file so that a crash mid-write can't leave it half written.
+fn write_replacing(path: &Path, bytes: &[u8]) -> Result<(), SaveError> {
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, bytes)?;
+    fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+/// Identifies a palette entry. Floats are compared by their bits so that loading gives back
+/// exactly the values that were saved.
+type PaletteKey = (u8, u32, Option<[u32; 3]>);
+
+fn palette_key(block: &Block) -> PaletteKey {
+    (
+        block.block_type().id(),
+        block.health().to_bits(),
+        block
+            .position_offset()
+            .map(|o| [o.x.to_bits(), o.y.to_bits(), o.z.to_bits()]),
+    )
+}
+
+/// Encodes the blocks of the chunk into a chunk payload.
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let mut palette: Vec<Block> = Vec::new();
+    let mut palette_indices: HashMap<PaletteKey, u16> = HashMap::new();
+    let mut runs: Vec<(u16, u16)> = Vec::new();
+
+    for i in 0..CHUNK_BLOCK_WIDTH {
+        for j in 0..CHUNK_BLOCK_WIDTH {
+            for k in 0..CHUNK_BLOCK_WIDTH {
+                let block = chunk.at(i, j, k);
+                let palette_index =
+                    *palette_indices
+                        .entry(palette_key(&block))
+                        .or_insert_with(|| {
+                            palette.push(block);
+                            palette.len() as u16 - 1
+                        });
+
+                match runs.last_mut() {
+                    Some((index, length)) if *index == palette_index && *length < u16::MAX => {
+                        *length += 1
+                    }
+                    _ => runs.push((palette_index, 1)),
+                }
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+    for block in &palette {
+        block.write_to(&mut bytes);
+    }
+
+    bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+    for (index, length) in runs {
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Decodes a chunk payload into the chunk at the chunk coordinates.
+pub fn decode_chunk(coords: ChunkCoords, payload: &[u8]) -> Result<Chunk, SaveError> {
+    let mut reader = Reader::new(payload);
+
+    let palette_size = reader.read_u16()?;
+    let mut palette = Vec::with_capacity(palette_size as usize);
+    for _ in 0..palette_size {
+        palette.push(Block::read_from(&mut reader)?);
+    }
+
+    let mut chunk = Chunk::empty(coords.0, coords.1, coords.2);
+    let block_count = CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH;
+    let mut position = 0;
+
+    let run_count = reader.read_u32()?;
+    for _ in 0..run_count {
+        let index = reader.read_u16()? as usize;
+        let length = reader.read_u16()? as usize;
+
+        let block = *palette.get(index).ok_or_else(|| {
+            SaveError::Corrupt(format!(
+                "palette index {} out of {} entries",
+                index, palette_size
+            ))
+        })?;
+        if position + length > block_count {
+            return Err(SaveError::Corrupt(format!(
+                "chunk {:?} has more than {} blocks",
+                coords, block_count
+            )));
+        }
+
+        for p in position..position + length {
+            let (i, j, k) = (
+                p / (CHUNK_BLOCK_WIDTH * CHUNK_BLOCK_WIDTH),
+                p / CHUNK_BLOCK_WIDTH % CHUNK_BLOCK_WIDTH,
+                p % CHUNK_BLOCK_WIDTH,
+            );
+            chunk.set(i, j, k, block);
+        }
+        position += length;
+    }
+
+    if position != block_count {
+        return Err(SaveError::Corrupt(format!(
+            "chunk {:?} has {} of {} blocks",
+            coords, position, block_count
+        )));
+    }
+
+    Ok(chunk)
+}
+
+impl Persist for Vector3<f32> {
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.x.to_le_bytes());
+        bytes.extend_from_slice(&self.y.to_le_bytes());
+        bytes.extend_from_slice(&self.z.to_le_bytes());
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Vector3::new(
+            reader.read_f32()?,
+            reader.read_f32()?,
+            reader.read_f32()?,
+        ))
+    }
+}
+
+/// Reads little-endian values from a byte slice, failing with `SaveError::Corrupt` instead of
+/// running off its end.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], SaveError> {
+        let end = self.position + count;
+        if end > self.bytes.len() {
+            return Err(SaveError::Corrupt(format!(
+                "expected {} more bytes at offset {}, but only {} are left",
+                count,
+                self.position,
+                self.bytes.len() - self.position
+            )));
+        }
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveError> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveError> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, SaveError> {
+        Ok(i64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, SaveError> {
+        Ok(f32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    /// Reads a bool written as a single byte.
+    pub fn read_bool(&mut self) -> Result<bool, SaveError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(SaveError::Corrupt(format!("{} isn't a bool", other))),
+        }
+    }
+
+    pub fn read_block_type(&mut self) -> Result<BlockType, SaveError> {
+        let id = self.read_u8()?;
+        BlockType::from_id(id).ok_or_else(|| SaveError::Corrupt(format!("unknown block id {}", id)))
+    }
+
+    /// Reads a block index within a chunk.
+    fn read_index(&mut self) -> Result<usize, SaveError> {
+        let index = self.read_u8()? as usize;
+        if index >= CHUNK_BLOCK_WIDTH {
+            return Err(SaveError::Corrupt(format!(
+                "block index {} is outside of a chunk",
+                index
+            )));
+        }
+        Ok(index)
+    }
+
+    /// Checks the magic bytes and format version that every save file starts with.
+    fn expect_header(&mut self, magic: &[u8; 4], path: &Path) -> Result<(), SaveError> {
+        if self.read_bytes(4).ok() != Some(&magic[..]) {
+            return Err(SaveError::BadMagic(path.to_path_buf()));
+        }
+
+        let version = self.read_u16()?;
+        if version != FORMAT_VERSION {
+            return Err(SaveError::UnsupportedVersion(path.to_path_buf(), version));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COORDS: ChunkCoords = (3, -1, 2);
+
+    /// Encodes and decodes the chunk, checking that every block comes back exactly as it was.
+    fn assert_round_trips(chunk: &Chunk) {
+        let decoded = decode_chunk(COORDS, &encode_chunk(chunk)).unwrap();
+        assert_same_blocks(&decoded, chunk);
+    }
+
+    fn assert_same_blocks(decoded: &Chunk, chunk: &Chunk) {
+        assert_eq!(decoded.content_hash(), chunk.content_hash());
+
+        for i in 0..CHUNK_BLOCK_WIDTH {
+            for j in 0..CHUNK_BLOCK_WIDTH {
+                for k in 0..CHUNK_BLOCK_WIDTH {
+                    assert_eq!(
+                        palette_key(&decoded.at(i, j, k)),
+                        palette_key(&chunk.at(i, j, k)),
+                        "block ({}, {}, {})",
+                        i,
+                        j,
+                        k
+                    );
+                }
+            }
+        }
+    }
+
+    fn filled_chunk(block: Block) -> Chunk {
+        let mut chunk = Chunk::empty(COORDS.0, COORDS.1, COORDS.2);
+        for i in 0..CHUNK_BLOCK_WIDTH {
+            for j in 0..CHUNK_BLOCK_WIDTH {
+                for k in 0..CHUNK_BLOCK_WIDTH {
+                    chunk.set(i, j, k, block);
+                }
+            }
+        }
+        chunk
+    }
+
+    fn mixed_chunk() -> Chunk {
+        let mut chunk = filled_chunk(Block::from(BlockType::Stone));
+        for i in 0..CHUNK_BLOCK_WIDTH {
+            for k in 0..CHUNK_BLOCK_WIDTH {
+                chunk.set(i, CHUNK_BLOCK_WIDTH - 1, k, Block::from(BlockType::Air));
+                chunk.set(i, (i + k) % 7, k, Block::from(BlockType::Dirt));
+            }
+        }
+
+        chunk.set(4, 5, 6, Block::new(BlockType::Stone, 0.25, None));
+        chunk.set(
+            7,
+            8,
+            9,
+            Block::new(BlockType::Grass, 1.5, Some(Vector3::new(0.1, -0.2, 0.3))),
+        );
+        chunk.set(31, 0, 31, Block::from(BlockType::Crystal));
+        chunk
+    }
+
+    /// Makes an empty save in a directory of its own.
+    fn save_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("chicky-save-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        WorldSave::create(&directory, 0).unwrap();
+        directory
+    }
+
+    /// Writes a region file for the chunk and tries to load the chunk from it.
+    fn load_from_region(name: &str, region: &[u8]) -> Result<Option<Chunk>, SaveError> {
+        let directory = save_directory(name);
+        let (region_coords, _) = split_chunk_coords(COORDS);
+        fs::write(region_path(&directory, region_coords), region).unwrap();
+
+        let (mut save, _, _) = WorldSave::open(&directory).unwrap();
+        let result = save.load_chunk(COORDS);
+        fs::remove_dir_all(&directory).unwrap();
+        result
+    }
+
+    #[test]
+    fn empty_chunks_round_trip() {
+        assert_round_trips(&Chunk::empty(COORDS.0, COORDS.1, COORDS.2));
+    }
+
+    #[test]
+    fn full_chunks_round_trip() {
+        assert_round_trips(&filled_chunk(Block::from(BlockType::Stone)));
+    }
+
+    #[test]
+    fn mixed_chunks_round_trip() {
+        let chunk = mixed_chunk();
+        assert_round_trips(&chunk);
+
+        let decoded = decode_chunk(COORDS, &encode_chunk(&chunk)).unwrap();
+        assert_eq!(decoded.at(4, 5, 6).health(), 0.25);
+        assert_eq!(
+            decoded.at(7, 8, 9).position_offset(),
+            Some(Vector3::new(0.1, -0.2, 0.3))
+        );
+    }
+
+    #[test]
+    fn chunks_round_trip_through_region_files() {
+        let directory = save_directory("region");
+        let chunk = mixed_chunk();
+
+        let (mut writer, _, _) = WorldSave::open(&directory).unwrap();
+        writer.store_chunk(COORDS, &chunk).unwrap();
+        writer.flush(0, &HashMap::new()).unwrap();
+
+        let (mut reader, _, _) = WorldSave::open(&directory).unwrap();
+        let loaded = reader.load_chunk(COORDS).unwrap().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_same_blocks(&loaded, &chunk);
+    }
+
+    #[test]
+    fn regions_are_written_and_forgotten_once_unused() {
+        let directory = save_directory("evict");
+        let chunk = mixed_chunk();
+        let (region_coords, _) = split_chunk_coords(COORDS);
+
+        let (mut save, _, _) = WorldSave::open(&directory).unwrap();
+        save.store_chunk(COORDS, &chunk).unwrap();
+
+        // a loaded chunk keeps its region around, unwritten
+        save.evict_regions(vec![COORDS], 0, &HashMap::new())
+            .unwrap();
+        assert!(save.regions.contains_key(&region_coords));
+        assert!(!region_path(&directory, region_coords).exists());
+
+        save.evict_regions(Vec::new(), 0, &HashMap::new()).unwrap();
+        assert!(save.regions.is_empty());
+
+        // the region was written on the way out, without a flush
+        let (mut reader, _, _) = WorldSave::open(&directory).unwrap();
+        let loaded = reader.load_chunk(COORDS).unwrap().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_same_blocks(&loaded, &chunk);
+    }
+
+    #[test]
+    fn truncated_chunks_are_corrupt() {
+        let payload = encode_chunk(&mixed_chunk());
+
+        for &length in &[0, 1, 2, payload.len() / 2, payload.len() - 1] {
+            match decode_chunk(COORDS, &payload[..length]) {
+                Err(SaveError::Corrupt(_)) => {}
+                other => panic!("{} bytes: {:?}", length, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn runs_past_the_end_of_the_chunk_are_corrupt() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&1u16.to_le_bytes());
+        Block::from(BlockType::Stone).write_to(&mut payload);
+
+        // two runs that add up to more blocks than a chunk has
+        payload.extend_from_slice(&2u32.to_le_bytes());
+        for _ in 0..2 {
+            payload.extend_from_slice(&0u16.to_le_bytes());
+            payload.extend_from_slice(&u16::MAX.to_le_bytes());
+        }
+
+        assert!(matches!(
+            decode_chunk(COORDS, &payload),
+            Err(SaveError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn region_files_with_bad_magic_are_rejected() {
+        let mut region = b"NOPE".to_vec();
+        region.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        region.extend_from_slice(&0u16.to_le_bytes());
+
+        assert!(matches!(
+            load_from_region("magic", &region),
+            Err(SaveError::BadMagic(_))
+        ));
+    }
+
+    #[test]
+    fn region_files_from_other_versions_are_rejected() {
+        let mut region = REGION_MAGIC.to_vec();
+        region.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        region.extend_from_slice(&0u16.to_le_bytes());
+
+        match load_from_region("version", &region) {
+            Err(SaveError::UnsupportedVersion(_, version)) => {
+                assert_eq!(version, FORMAT_VERSION + 1)
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
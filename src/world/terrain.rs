@@ -0,0 +1,192 @@
+use super::biome::{Biome, BiomeClassifier};
+use super::caves::CaveCarver;
+use super::ChunkCoords;
+use crate::blocks::{BlockType, CHUNK_BLOCK_WIDTH};
+use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
+
+/// The height, in blocks, of the ocean surface.
+pub const SEA_LEVEL: i64 = 0;
+
+/// How many blocks the base elevation field is scaled by. An elevation of 1 would put the base of
+/// a column this many blocks above sea level.
+const ELEVATION_SCALE: f64 = 64.0;
+
+/// How many blocks of subsurface (usually dirt) lie between the surface and the stone below it.
+const SUBSURFACE_DEPTH: i64 = 3;
+
+/// Distance, in blocks, between the neighboring columns that are sampled to smooth out how much
+/// the surface varies across biome borders.
+const BLEND_SPACING: i64 = 8;
+
+/// A column of terrain: its biome and the height of its topmost solid block.
+#[derive(Debug, Copy, Clone)]
+pub struct TerrainColumn {
+    pub biome: Biome,
+    pub surface_height: i64,
+}
+
+/// Generates terrain from a seed. The same seed always gives the same terrain.
+pub struct TerrainGenerator {
+    seed: i64,
+    biomes: BiomeClassifier,
+    caves: CaveCarver,
+    detail_noise: Fbm,
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: i64) -> Self {
+        let folded_seed = fold_seed(seed);
+        Self {
+            seed,
+            biomes: BiomeClassifier::new(folded_seed),
+            caves: CaveCarver::new(folded_seed),
+            detail_noise: Fbm::new()
+                .set_seed(folded_seed)
+                .set_octaves(4)
+                .set_frequency(0.01),
+        }
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    pub fn biomes(&self) -> &BiomeClassifier {
+        &self.biomes
+    }
+
+    pub fn caves(&self) -> &CaveCarver {
+        &self.caves
+    }
+
+    /// Returns the biome and surface height of the column at the world block position.
+    pub fn column_at(&self, x: i64, z: i64) -> TerrainColumn {
+        let climate = self.biomes.climate_at(x, z);
+
+        // average the height variation of the surrounding biomes so the surface doesn't jump
+        // where one biome meets another
+        let mut variation = 0.0;
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                let neighbor = if dx == 0 && dz == 0 {
+                    climate.biome
+                } else {
+                    self.biomes
+                        .biome_at(x + dx * BLEND_SPACING, z + dz * BLEND_SPACING)
+                };
+                variation += neighbor.height_variation();
+            }
+        }
+        variation /= 9.0;
+
+        let detail = self.detail_noise.get([x as f64, z as f64]);
+        let mut surface_height =
+            (climate.elevation * ELEVATION_SCALE + detail * variation).floor() as i64 + SEA_LEVEL;
+
+        if let Some(step) = climate.biome.terrace_height() {
+            surface_height = surface_height.div_euclid(step) * step;
+        }
+
+        TerrainColumn {
+            biome: climate.biome,
+            surface_height,
+        }
+    }
+
+    /// Returns every column of the chunk column at the chunk grid position, indexed by the
+    /// block's array position within the chunk.
+    pub fn columns_for_chunk(
+        &self,
+        chunk_i: i64,
+        chunk_k: i64,
+    ) -> [[TerrainColumn; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH] {
+        let mut columns = [[TerrainColumn {
+            biome: Biome::Plains,
+            surface_height: SEA_LEVEL,
+        }; CHUNK_BLOCK_WIDTH]; CHUNK_BLOCK_WIDTH];
+
+        let (origin_x, origin_z) = (
+            chunk_i * CHUNK_BLOCK_WIDTH as i64,
+            chunk_k * CHUNK_BLOCK_WIDTH as i64,
+        );
+        for (i, row) in columns.iter_mut().enumerate() {
+            for (k, column) in row.iter_mut().enumerate() {
+                *column = self.column_at(origin_x + i as i64, origin_z + k as i64);
+            }
+        }
+
+        columns
+    }
+
+    /// Returns the height of the topmost solid block in the column at the world block position.
+    pub fn height_at(&self, x: i64, z: i64) -> i64 {
+        self.column_at(x, z).surface_height
+    }
+
+    /// Returns the type of block that the terrain leaves at the world block position once caves
+    /// have been carved out of it, before ores and decoration. Used to look at blocks in chunks
+    /// that haven't been generated yet.
+    pub fn carved_block_type_at(
+        &self,
+        x: i64,
+        y: i64,
+        z: i64,
+        column: &TerrainColumn,
+    ) -> BlockType {
+        let ty = self.block_type_at(y, column);
+        let solid = !matches!(ty, BlockType::Air | BlockType::Water | BlockType::Ice);
+        if solid && self.caves.is_carved(x, y, z, column) {
+            BlockType::Air
+        } else {
+            ty
+        }
+    }
+
+    /// Returns the type of block at height `y` in the column.
+    pub fn block_type_at(&self, y: i64, column: &TerrainColumn) -> BlockType {
+        let surface_height = column.surface_height;
+
+        if y > surface_height {
+            if y > SEA_LEVEL {
+                BlockType::Air
+            } else if y == SEA_LEVEL && column.biome == Biome::FrozenOcean {
+                BlockType::Ice
+            } else {
+                BlockType::Water
+            }
+        } else if y == surface_height {
+            if surface_height < SEA_LEVEL {
+                // nothing grows at the bottom of the sea
+                BlockType::Sand
+            } else {
+                column.biome.surface_block()
+            }
+        } else if y >= surface_height - SUBSURFACE_DEPTH {
+            column.biome.subsurface_block()
+        } else {
+            BlockType::Stone
+        }
+    }
+}
+
+/// Mixes the world seed, chunk coordinates and a salt into a seed for a random number generator,
+/// so each generation step gets its own independent, repeatable randomness in each chunk.
+pub fn mix_seed(seed: i64, coords: ChunkCoords, salt: u64) -> u64 {
+    // splitmix64 finalizer, applied after each input
+    fn mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    let mut h = mix(seed as u64 ^ salt);
+    h = mix(h ^ coords.0 as u64);
+    h = mix(h ^ coords.1 as u64);
+    mix(h ^ coords.2 as u64)
+}
+
+/// Folds a 64-bit world seed into the 32-bit seed that noise functions take, so that both halves
+/// of the world seed matter.
+fn fold_seed(seed: i64) -> u32 {
+    (seed ^ (seed >> 32)) as u32
+}